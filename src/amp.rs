@@ -1,72 +1,367 @@
-use reqwest::{Client, StatusCode};
+use std::sync::Arc;
+use std::time::Duration;
 
-use crate::entities::{ApiOptions, Event, UploadBody};
-use crate::response::AmplitudeResponse;
+use crate::breaker::{BreakerPolicy, BreakerState, CircuitBreaker};
+use crate::builder::AmpBuilder;
+use crate::callbacks::{Callbacks, Cause};
+use crate::dead_letter::{DeadLetter, DeadLetterSink, Failure};
+use crate::entities::{ApiOptions, Event, InsertIds, UploadBody};
+use crate::key::{ApiKey, KeyStore};
+use crate::limiter::{LimiterStats, RateLimiter, RateLimits};
+use crate::recorder::Recorder;
+use crate::report::{Reason, Report};
+use crate::response::{AmplitudeResponse, Unexpected};
+use crate::retry::{RetryPolicy, ThrottlePolicy};
+use crate::server::{Endpoint, ServerZone};
+use crate::stats::{Metrics, Stats};
+use crate::transport::{Request, Transport};
+use futures_util::{stream, Stream, StreamExt};
+use std::collections::VecDeque;
+use tokio::time::Instant;
 
 use super::*;
 
 #[derive(Clone, Debug)]
 pub struct Amp {
-    api_key: String,
-    client: Client,
-    url: String,
+    api_key: Arc<KeyStore>,
+    transport: Arc<dyn Transport>,
+    base_url: String,
+    endpoint: Endpoint,
     options: Option<ApiOptions>,
+    retry: Option<RetryPolicy>,
+    throttle: Option<ThrottlePolicy>,
+    split_too_large: bool,
+    drop_invalid: bool,
+    limits: Limits,
+    concurrency: usize,
+    limiter: Option<Arc<RateLimiter>>,
+    dead_letters: Option<Arc<dyn DeadLetterSink>>,
+    dead_letter_exhausted: bool,
+    recorder: Option<Recorder>,
+    breaker: Option<Arc<CircuitBreaker>>,
+    callbacks: Callbacks,
+    metrics: Arc<Metrics>,
+    timeout: Option<Duration>,
+    defaults: Option<Event>,
+    insert_ids: Option<InsertIds>,
+    #[cfg(feature = "gzip")]
+    gzip: Option<u32>,
 }
 
-impl Amp {
-    const URL_SINGLE: &'static str = "https://api2.amplitude.com/2/httpapi";
-    const URL_BATCH: &'static str = "https://api2.amplitude.com/batch";
-    const DEFAULT_SERVER_ERROR: &'static str = r#"{"error": "Some kind of server error"}"#;
-    const ENV: &'static str = "AMPLITUDE_API_KEY";
+/// Maximum size of a request accepted by an endpoint
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Limits {
+    /// Maximum number of events in a request
+    pub max_events: usize,
+    /// Maximum size of a serialized request in bytes
+    pub max_bytes: usize,
+}
+
+impl Limits {
+    /// [HTTP API V2 limits](https://developers.amplitude.com/docs/http-api-v2#upload-limit)
+    pub const SINGLE: Limits = Limits {
+        max_events: 2000,
+        max_bytes: 1024 * 1024,
+    };
+    /// [Batch Event Upload API limits](https://developers.amplitude.com/docs/batch-event-upload-api#feature-comparison-between-httpapi-2httpapi--batch)
+    pub const BATCH: Limits = Limits {
+        max_events: 2000,
+        max_bytes: 20 * 1024 * 1024,
+    };
+}
 
+impl Amp {
+    /// Creates a client from the `AMPLITUDE_*` environment variables,
+    /// see [AmpBuilder::from_env](AmpBuilder::from_env)
     pub fn from_env() -> Result<Self, AmplitudeError> {
-        let client = reqwest::Client::new();
-        let api_key = std::env::var(Self::ENV);
-        if let Ok(api_key) = api_key {
-            Ok(Self {
-                api_key,
-                client,
-                url: Self::URL_SINGLE.into(),
-                options: None,
-            })
-        } else {
-            let err = "No AMPLITUDE_API_KEY environment variable was found".to_string();
-            Err(AmplitudeError::InitializationError(err))
-        }
+        AmpBuilder::from_env()?.build()
+    }
+
+    /// A builder to configure the client at once
+    pub fn builder<S>(api_key: S) -> AmpBuilder
+    where
+        S: Into<ApiKey>,
+    {
+        AmpBuilder::new(api_key)
     }
 
     pub fn new<S>(api_key: S) -> Self
     where
-        S: Into<String>,
+        S: Into<ApiKey>,
     {
-        let api_key = api_key.into();
-        let client = reqwest::Client::new();
+        let api_key = Arc::new(KeyStore::new(api_key.into()));
+        #[cfg(feature = "reqwest")]
+        let transport = Arc::new(reqwest::Client::new());
+        #[cfg(not(feature = "reqwest"))]
+        let transport = Arc::new(crate::transport::NoTransport);
         Self {
             api_key,
-            client,
-            url: Self::URL_SINGLE.into(),
+            transport,
+            base_url: ServerZone::default().base_url().into(),
+            endpoint: Endpoint::Single,
             options: None,
+            retry: None,
+            throttle: None,
+            split_too_large: false,
+            drop_invalid: false,
+            limits: Limits::SINGLE,
+            concurrency: 1,
+            limiter: None,
+            dead_letters: None,
+            dead_letter_exhausted: true,
+            recorder: None,
+            breaker: None,
+            callbacks: Callbacks::default(),
+            metrics: Arc::default(),
+            timeout: None,
+            defaults: None,
+            insert_ids: None,
+            #[cfg(feature = "gzip")]
+            gzip: None,
         }
     }
 
+    /// Replaces the api key of this client and all its clones, e.g. the ones
+    /// sending in the background. A [provider](Amp::set_api_key_provider)
+    /// replaces it again with its next key
+    pub fn rotate_api_key<S>(&self, api_key: S)
+    where
+        S: Into<ApiKey>,
+    {
+        self.api_key.set(api_key.into());
+    }
+
+    /// Takes the api key from the function, asking it again every time the refresh interval
+    /// passes. Fails if the function fails right away, later failures keep the previous key.
+    /// Later calls run in the background on a blocking thread of the tokio runtime,
+    /// the requests are sent with the previous key meanwhile.
+    /// Clones made before this call keep their key
+    ///
+    /// ```no_run
+    /// # fn main() -> Result<(), amplitude::AmplitudeError> {
+    /// use std::time::Duration;
+    /// use amplitude::{Amp, ApiKey};
+    ///
+    /// let mut amp = Amp::new("");
+    /// amp.set_api_key_provider(Duration::from_secs(60), || {
+    ///     ApiKey::from_file("/run/secrets/amplitude")
+    /// })?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn set_api_key_provider<F>(
+        &mut self,
+        refresh: Duration,
+        provider: F,
+    ) -> Result<&mut Self, AmplitudeError>
+    where
+        F: Fn() -> Result<ApiKey, AmplitudeError> + Send + Sync + 'static,
+    {
+        self.api_key = Arc::new(KeyStore::with_provider(Box::new(provider), refresh)?);
+        Ok(self)
+    }
+
     /// Sets new [client](https://docs.rs/reqwest/0.10.2/reqwest/struct.Client.html)
+    #[cfg(feature = "reqwest")]
     pub fn set_client(&mut self, client: reqwest::Client) -> &mut Self {
-        self.client = client;
+        self.set_transport(client)
+    }
+
+    /// Sets an HTTP client to post requests with
+    pub fn set_transport<T>(&mut self, transport: T) -> &mut Self
+    where
+        T: Transport + 'static,
+    {
+        self.transport = Arc::new(transport);
         self
     }
 
     /// Sets HTTP API V2 (Single) url to send request to
     pub fn single(&mut self) -> &mut Self {
-        self.url = Self::URL_SINGLE.into();
+        self.endpoint = Endpoint::Single;
+        self.limits = Limits::SINGLE;
         self
     }
 
     /// Sets batch url
     pub fn batch(&mut self) -> &mut Self {
-        self.url = Self::URL_BATCH.into();
+        self.endpoint = Endpoint::Batch;
+        self.limits = Limits::BATCH;
+        self
+    }
+
+    /// Sets custom request limits. [single](Amp::single) and [batch](Amp::batch)
+    /// set the limits of the corresponding endpoint. A request takes at least one event
+    pub fn set_limits(&mut self, limits: Limits) -> &mut Self {
+        self.limits = Limits {
+            max_events: limits.max_events.max(1),
+            ..limits
+        };
+        self
+    }
+
+    /// Sets how many requests may be in flight at once when events are split
+    /// into several requests. 1 by default
+    pub fn set_concurrency(&mut self, requests: usize) -> &mut Self {
+        self.concurrency = requests.max(1);
+        self
+    }
+
+    /// Sends events to the data center of the zone
+    pub fn set_server_zone(&mut self, zone: ServerZone) -> &mut Self {
+        self.base_url = zone.base_url().into();
+        self
+    }
+
+    /// Sends events to a custom server, e.g. a relay or a local mock.
+    /// The endpoint path (`/2/httpapi` or `/batch`) is appended to the url
+    pub fn set_base_url<S>(&mut self, base_url: S) -> &mut Self
+    where
+        S: Into<String>,
+    {
+        self.base_url = base_url.into();
+        self
+    }
+
+    /// Url the requests are sent to
+    pub fn url(&self) -> String {
+        format!(
+            "{}{}",
+            self.base_url.trim_end_matches('/'),
+            self.endpoint.path()
+        )
+    }
+
+    /// Compresses requests with gzip of the given level (0 - 9).
+    /// The [limits](Amp::set_limits) are then checked against the compressed size
+    #[cfg(feature = "gzip")]
+    pub fn gzip(&mut self, level: u32) -> &mut Self {
+        self.gzip = Some(level);
+        self
+    }
+
+    /// Delays events which would exceed the limits, before they are sent.
+    /// The state of the limiter is shared by all clones of this client
+    pub fn set_rate_limits(&mut self, limits: RateLimits) -> &mut Self {
+        self.limiter = Some(Arc::new(RateLimiter::new(limits)));
+        self
+    }
+
+    /// What the rate limiter has done so far, if there is one
+    pub fn rate_limiter_stats(&self) -> Option<LimiterStats> {
+        self.limiter.as_ref().map(|limiter| limiter.stats())
+    }
+
+    /// Records the events which could not be delivered for good to the sink:
+    /// the ones rejected as invalid or too large, and the ones which were still not
+    /// delivered once the [retry policy](Amp::set_retry_policy) or the
+    /// [throttle policy](Amp::set_throttle_policy) gave up, or while the
+    /// [circuit](Amp::set_circuit_breaker) is open. A retryable failure without
+    /// such a policy is left to the caller. Failures to record are ignored
+    pub fn set_dead_letter_sink<T>(&mut self, sink: T) -> &mut Self
+    where
+        T: DeadLetterSink + 'static,
+    {
+        self.dead_letters = Some(Arc::new(sink));
         self
     }
 
+    /// When disabled, the events which exhausted the retries, were not sent because the
+    /// [circuit](Amp::set_circuit_breaker) is open or were rejected for the api key are not
+    /// recorded to the [dead letter sink](Amp::set_dead_letter_sink), only the other rejected
+    /// ones are.
+    /// For callers which keep such events to send them again later,
+    /// like [PersistentQueue](crate::PersistentQueue). Enabled by default
+    pub fn dead_letter_exhausted(&mut self, enabled: bool) -> &mut Self {
+        self.dead_letter_exhausted = enabled;
+        self
+    }
+
+    /// Dry run: the requests are given to the recorder instead of being posted
+    /// and every one of them succeeds
+    pub fn set_recorder(&mut self, recorder: Recorder) -> &mut Self {
+        self.recorder = Some(recorder);
+        self
+    }
+
+    /// Stops sending requests for a while after repeated server or network failures.
+    /// Meanwhile [send](Amp::send) fails with [CircuitOpen](AmplitudeError::CircuitOpen),
+    /// the events go to the [dead letter sink](Amp::set_dead_letter_sink) or stay
+    /// in a [PersistentQueue](crate::PersistentQueue). Clones share the breaker
+    pub fn set_circuit_breaker(&mut self, policy: BreakerPolicy) -> &mut Self {
+        self.breaker = Some(Arc::new(CircuitBreaker::new(policy)));
+        self
+    }
+
+    /// State of the circuit breaker, if there is one
+    pub fn circuit_state(&self) -> Option<BreakerState> {
+        self.breaker.as_ref().map(|breaker| breaker.state())
+    }
+
+    /// Calls the function with the events accepted by the amplitude servers
+    pub fn on_delivered<F>(&mut self, callback: F) -> &mut Self
+    where
+        F: Fn(&[Event], &AmplitudeResponse) + Send + Sync + 'static,
+    {
+        self.callbacks.delivered = Some(Arc::new(callback));
+        self
+    }
+
+    /// Calls the function with the events which were not delivered for good
+    pub fn on_failed<F>(&mut self, callback: F) -> &mut Self
+    where
+        F: Fn(&[Event], Cause<'_>) + Send + Sync + 'static,
+    {
+        self.callbacks.failed = Some(Arc::new(callback));
+        self
+    }
+
+    /// Calls the function before the events are sent once again, either by the
+    /// [retry policy](Amp::set_retry_policy) or the [throttle policy](Amp::set_throttle_policy).
+    /// The number is the one of the failed attempt or resend, starting with 1
+    pub fn on_retry<F>(&mut self, callback: F) -> &mut Self
+    where
+        F: Fn(&[Event], u32, Cause<'_>) + Send + Sync + 'static,
+    {
+        self.callbacks.retry = Some(Arc::new(callback));
+        self
+    }
+
+    /// What this client and its clones have done so far
+    pub fn stats(&self) -> Stats {
+        self.metrics.snapshot()
+    }
+
+    pub(crate) fn metrics(&self) -> &Arc<Metrics> {
+        &self.metrics
+    }
+
+    /// Gives up a request which takes longer than the timeout with a
+    /// [NetworkError](AmplitudeError::NetworkError)
+    pub fn set_timeout(&mut self, timeout: Duration) -> &mut Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Fields every event has unless it sets them itself.
+    /// The time, event_id and insert_id of the defaults are ignored
+    pub fn set_default_event(&mut self, defaults: Event) -> &mut Self {
+        self.defaults = Some(defaults);
+        self
+    }
+
+    /// Gives every event without an insert_id one before it is sent for the first time,
+    /// so the amplitude servers deduplicate the retries and the events sent once again,
+    /// e.g. from a [dead letter file](crate::dead_letter::redrive)
+    pub fn set_insert_ids(&mut self, ids: InsertIds) -> &mut Self {
+        self.insert_ids = Some(ids);
+        self
+    }
+
+    pub(crate) fn insert_ids(&self) -> Option<InsertIds> {
+        self.insert_ids
+    }
+
     /// Sets minimum permitted length for user_id & device_id fields
     pub fn set_min_id_length(&mut self, length: u16) -> &mut Self {
         if self.options.is_none() {
@@ -76,48 +371,540 @@ impl Amp {
         self
     }
 
-    /// Sends bunch of events to the amplitude servers
-    pub async fn send(&self, events: Vec<Event>) -> Result<AmplitudeResponse, AmplitudeError> {
-        let upload_body = UploadBody {
-            api_key: self.api_key.clone(),
-            events,
-            options: self.options.clone(),
+    /// Retries uploads which failed with a retryable outcome according to the policy
+    pub fn set_retry_policy(&mut self, policy: RetryPolicy) -> &mut Self {
+        self.retry = Some(policy);
+        self
+    }
+
+    /// Resends events rejected because of throttling according to the policy
+    pub fn set_throttle_policy(&mut self, policy: ThrottlePolicy) -> &mut Self {
+        self.throttle = Some(policy);
+        self
+    }
+
+    /// When enabled, a request rejected with
+    /// [PayloadTooLarge](AmplitudeResponse::PayloadTooLarge) is split in halves
+    /// which are sent separately, until a single event turns out to be too large
+    pub fn split_too_large(&mut self, enabled: bool) -> &mut Self {
+        self.split_too_large = enabled;
+        self
+    }
+
+    /// When enabled, the events pointed to by a [BadRequest](AmplitudeResponse::BadRequest)
+    /// response are dropped and the rest of the request is sent again.
+    /// The dropped events are reported in [Report::rejected](Report::rejected)
+    pub fn drop_invalid_events(&mut self, enabled: bool) -> &mut Self {
+        self.drop_invalid = enabled;
+        self
+    }
+
+    /// Sends bunch of events to the amplitude servers.
+    ///
+    /// Events are split into as many requests as the [limits](Amp::set_limits) require,
+    /// the outcome of all requests is merged into one report.
+    /// If any of the requests fails with an error, the error is returned once all of them
+    /// are done. When other requests were answered before, the error is
+    /// [PartiallySent](AmplitudeError::PartiallySent) with their report and the events
+    /// which were not sent. With [rate limits](Amp::set_rate_limits) the events exceeding
+    /// them are sent later
+    pub async fn send(&self, mut events: Vec<Event>) -> Result<Report, AmplitudeError> {
+        if let Some(defaults) = &self.defaults {
+            for event in &mut events {
+                event.fill_defaults(defaults);
+            }
+        }
+        if let Some(ids) = self.insert_ids {
+            for event in &mut events {
+                event.assign_insert_id(ids);
+            }
+        }
+        let limiter = match &self.limiter {
+            Some(limiter) => limiter,
+            None => return self.send_chunks(events).await.into_result(),
         };
-        self._send(upload_body).await
+        // every wave is sent even if an earlier one fails, their slots are reserved already
+        let mut outcome = Outcome::default();
+        for (at, events) in limiter.schedule(events) {
+            tokio::time::sleep_until(at).await;
+            outcome.merge(self.send_chunks(events).await);
+        }
+        outcome.into_result()
+    }
+
+    /// Sends events as they come from the stream, yielding the outcome of every batch.
+    ///
+    /// A batch takes the events which are ready at once, up to the
+    /// [maximum number](Limits::max_events) of a request. At most as many batches as the
+    /// [concurrency](Amp::set_concurrency) allows are sent at the same time, the stream is
+    /// not polled for more events meanwhile. Outcomes are yielded in the order of batches
+    pub fn send_stream<'a, S>(
+        &'a self,
+        events: S,
+    ) -> impl Stream<Item = Result<Report, AmplitudeError>> + 'a
+    where
+        S: Stream<Item = Event> + 'a,
+    {
+        events
+            .ready_chunks(self.limits.max_events.max(1))
+            .map(move |events| self.send(events))
+            .buffered(self.concurrency)
+    }
+
+    /// Sends events in as many requests as the limits of the endpoint require
+    async fn send_chunks(&self, events: Vec<Event>) -> Outcome {
+        // every chunk is sent even if some of them fail, so no events are silently dropped
+        stream::iter(self.chunks(events))
+            .map(|chunk| self.deliver(chunk))
+            .buffered(self.concurrency)
+            .fold(Outcome::default(), |mut outcome, chunk| async move {
+                outcome.merge(chunk);
+                outcome
+            })
+            .await
+    }
+
+    /// Splits events into batches which fit into the limits of the endpoint
+    fn chunks(&self, events: Vec<Event>) -> Vec<Batch> {
+        #[cfg(feature = "gzip")]
+        if self.gzip.is_some() {
+            return self.compressed_chunks(events);
+        }
+        let envelope = self.upload_body(Vec::new()).size();
+        let mut chunks = Vec::new();
+        let mut chunk = Vec::new();
+        let mut bytes = envelope;
+        for event in events {
+            let size = UploadBody::event_size(&event);
+            if !chunk.is_empty()
+                && (chunk.len() >= self.limits.max_events || bytes + size > self.limits.max_bytes)
+            {
+                chunks.push(Batch::new(std::mem::take(&mut chunk)));
+                bytes = envelope;
+            }
+            bytes += size;
+            chunk.push(event);
+        }
+        if !chunk.is_empty() {
+            chunks.push(Batch::new(chunk));
+        }
+        chunks
+    }
+
+    /// Splits events by count first, then splits in halves every chunk
+    /// which is still too large after compression. The batches keep the compressed body
+    #[cfg(feature = "gzip")]
+    fn compressed_chunks(&self, mut events: Vec<Event>) -> Vec<Batch> {
+        let mut pending = Vec::new();
+        while events.len() > self.limits.max_events {
+            let rest = events.split_off(self.limits.max_events);
+            pending.push(events);
+            events = rest;
+        }
+        pending.push(events);
+        pending.reverse();
+        let mut chunks = Vec::new();
+        while let Some(chunk) = pending.pop() {
+            let upload_body = self.upload_body(chunk);
+            let body = self.encode(&upload_body).ok();
+            let mut chunk = upload_body.events;
+            let fits = matches!(&body, Some(body) if body.len() <= self.limits.max_bytes);
+            if chunk.len() > 1 && !fits {
+                let second = chunk.split_off(chunk.len() / 2);
+                pending.push(second);
+                pending.push(chunk);
+            } else if !chunk.is_empty() {
+                chunks.push(Batch {
+                    encoded: body,
+                    ..Batch::new(chunk)
+                });
+            }
+        }
+        chunks
+    }
+
+    /// Sends events which fit into one request, handling the responses
+    /// according to the configuration
+    async fn deliver(&self, batch: Batch) -> Outcome {
+        let mut report = Report::default();
+        let mut letters = Vec::new();
+        let mut pending = VecDeque::from(vec![batch]);
+        while let Some(mut batch) = pending.pop_front() {
+            if let Some(at) = batch.not_before {
+                tokio::time::sleep_until(at).await;
+            }
+            let upload_body = self.upload_body(std::mem::take(&mut batch.events));
+            let encoded = batch.encoded.take();
+            let (response, attempts) = match self
+                .send_with_retries(&upload_body, encoded.as_deref())
+                .await
+            {
+                Ok(result) => result,
+                Err(err) => {
+                    let events: Vec<_> = upload_body
+                        .events
+                        .into_iter()
+                        .chain(pending.into_iter().flat_map(|batch| batch.events))
+                        .collect();
+                    self.failed(&events, Cause::Error(&err));
+                    let exhausted = matches!(
+                        err,
+                        AmplitudeError::RetriesExhausted { .. } | AmplitudeError::CircuitOpen
+                    );
+                    if exhausted && self.dead_letter_exhausted {
+                        self.bury(&mut letters, &events, Failure::Error(err.to_string()));
+                    }
+                    self.dead_letter(letters);
+                    return Outcome {
+                        report,
+                        unsent: events,
+                        error: Some(err),
+                    };
+                }
+            };
+            report.attempts += attempts;
+            let events = upload_body.events;
+            match (response, &self.throttle) {
+                (AmplitudeResponse::TooManyRequests(throttled), Some(policy))
+                    if batch.resends < policy.max_resends =>
+                {
+                    let backoff = throttled
+                        .backoff()
+                        .unwrap_or(policy.max_backoff)
+                        .clamp(policy.min_backoff, policy.max_backoff);
+                    let (throttled_events, rest) = throttled.split(events);
+                    let response = AmplitudeResponse::TooManyRequests(throttled);
+                    self.retrying(
+                        &throttled_events,
+                        batch.resends + 1,
+                        Cause::Response(&response),
+                    );
+                    if !rest.is_empty() {
+                        pending.push_front(batch.resend(rest, None));
+                    }
+                    pending
+                        .push_back(batch.resend(throttled_events, Some(Instant::now() + backoff)));
+                }
+                (AmplitudeResponse::PayloadTooLarge(_), _) if self.split_too_large => {
+                    if events.len() == 1 {
+                        self.failed(&events, Cause::Rejected(&Reason::TooLarge));
+                        self.bury(&mut letters, &events, Failure::Rejected(Reason::TooLarge));
+                        report.reject(events, Reason::TooLarge);
+                    } else {
+                        let mut first = events;
+                        let second = first.split_off(first.len() / 2);
+                        pending.push_front(batch.part(second));
+                        pending.push_front(batch.part(first));
+                    }
+                }
+                (AmplitudeResponse::BadRequest(bad), _) if self.drop_invalid => {
+                    let (rejected, rest) = bad.split(events);
+                    if rejected.is_empty() {
+                        let response = AmplitudeResponse::BadRequest(bad);
+                        self.failed(&rest, Cause::Response(&response));
+                        let failure = Failure::Rejected(Reason::Response(response.clone()));
+                        self.bury(&mut letters, &rest, failure);
+                        report.fail(rest, response);
+                    } else {
+                        for rejected in &rejected {
+                            let events = std::slice::from_ref(&rejected.event);
+                            self.failed(events, Cause::Rejected(&rejected.reason));
+                            let failure = Failure::Rejected(rejected.reason.clone());
+                            self.bury(&mut letters, events, failure);
+                        }
+                        report.rejected.extend(rejected);
+                        if !rest.is_empty() {
+                            pending.push_front(batch.part(rest));
+                        }
+                    }
+                }
+                (AmplitudeResponse::Ok(ok), _) => {
+                    let response = AmplitudeResponse::Ok(ok);
+                    self.delivered(&events, &response);
+                    report.delivered.extend(events);
+                    report.responses.push(response);
+                }
+                (response, _) => {
+                    self.failed(&events, Cause::Response(&response));
+                    if self.gives_up(&response, attempts, batch.resends) {
+                        let failure = Failure::Rejected(Reason::Response(response.clone()));
+                        self.bury(&mut letters, &events, failure);
+                    }
+                    report.fail(events, response);
+                }
+            }
+        }
+        self.dead_letter(letters);
+        Outcome {
+            report,
+            ..Outcome::default()
+        }
+    }
+
+    /// Whether the final response means the events are not going to be delivered
+    /// by sending them again, so they belong to the dead letter sink
+    fn gives_up(&self, response: &AmplitudeResponse, attempts: u32, resends: u32) -> bool {
+        if response.is_unauthorized() {
+            return self.dead_letter_exhausted;
+        }
+        if let AmplitudeResponse::TooManyRequests(_) = response {
+            return self.dead_letter_exhausted
+                && matches!(&self.throttle, Some(policy) if resends >= policy.max_resends);
+        }
+        if response.is_retryable() {
+            return self.dead_letter_exhausted
+                && matches!(&self.retry, Some(policy) if attempts >= policy.max_attempts);
+        }
+        true
+    }
+
+    /// Adds dead letters of the events, if there is a sink to record them
+    fn bury(&self, letters: &mut Vec<DeadLetter>, events: &[Event], failure: Failure) {
+        if self.dead_letters.is_some() {
+            letters.extend(
+                events
+                    .iter()
+                    .map(|event| DeadLetter::new(event.clone(), failure.clone())),
+            );
+        }
+    }
+
+    fn delivered(&self, events: &[Event], response: &AmplitudeResponse) {
+        self.metrics.delivered(events.len());
+        self.callbacks.delivered(events, response);
+    }
+
+    fn failed(&self, events: &[Event], cause: Cause<'_>) {
+        self.metrics.failed(events.len());
+        self.callbacks.failed(events, cause);
+    }
+
+    fn retrying(&self, events: &[Event], attempt: u32, cause: Cause<'_>) {
+        self.metrics.retry();
+        self.callbacks.retry(events, attempt, cause);
+    }
+
+    fn dead_letter(&self, letters: Vec<DeadLetter>) {
+        if letters.is_empty() {
+            return;
+        }
+        if let Some(sink) = &self.dead_letters {
+            // the caller gets the failure anyway, a failing sink must not hide it
+            let _ = sink.record(letters);
+        }
     }
 
     /// Sends an event to the amplitude servers
-    pub async fn send_one(&self, event: Event) -> Result<AmplitudeResponse, AmplitudeError> {
+    pub async fn send_one(&self, event: Event) -> Result<Report, AmplitudeError> {
         self.send(vec![event]).await
     }
 
-    async fn _send(&self, upload_body: UploadBody) -> Result<AmplitudeResponse, AmplitudeError> {
-        let response = self
-            .client
-            .post(&self.url)
-            .json(&upload_body)
-            .send()
-            .await?;
-        let status = response.status();
-        let text = response
-            .text()
-            .await
-            .unwrap_or(Self::DEFAULT_SERVER_ERROR.into());
-        let amp_response = match status {
-            StatusCode::OK => Self::add_tag("Ok", text),
-            StatusCode::BAD_REQUEST => Self::add_tag("BadRequest", text),
-            StatusCode::PAYLOAD_TOO_LARGE => Self::add_tag("PayloadTooLarge", text),
-            StatusCode::TOO_MANY_REQUESTS => Self::add_tag("TooManyRequests", text),
-            StatusCode::INTERNAL_SERVER_ERROR
-            | StatusCode::BAD_GATEWAY
-            | StatusCode::GATEWAY_TIMEOUT => Self::add_tag("ServerError", text),
-            StatusCode::SERVICE_UNAVAILABLE => Self::add_tag("ServiceUnavailable", text),
-            _ => {
-                // should be unreachable
-                Self::add_tag("Ok", text)
+    fn upload_body(&self, events: Vec<Event>) -> UploadBody {
+        UploadBody {
+            api_key: self.api_key.current(),
+            events,
+            options: self.options.clone(),
+        }
+    }
+
+    /// Sends the body repeatedly while the outcome is retryable and the retry policy allows.
+    /// Returns the last response together with the number of attempts made
+    async fn send_with_retries(
+        &self,
+        upload_body: &UploadBody,
+        encoded: Option<&[u8]>,
+    ) -> Result<(AmplitudeResponse, u32), AmplitudeError> {
+        let policy = match &self.retry {
+            Some(policy) => policy,
+            None => return Ok((self.attempt(upload_body, encoded, 1).await?, 1)),
+        };
+        let mut attempt = 1;
+        loop {
+            let result = self.attempt(upload_body, encoded, attempt).await;
+            let retryable = match &result {
+                Ok(response) => response.is_retryable(),
+                Err(err) => err.is_retryable(),
+            };
+            if !retryable || attempt >= policy.max_attempts {
+                return match result {
+                    Ok(response) => Ok((response, attempt)),
+                    Err(err) if retryable => Err(AmplitudeError::RetriesExhausted {
+                        attempts: attempt,
+                        source: Box::new(err),
+                    }),
+                    Err(err) => Err(err),
+                };
             }
+            let cause = match &result {
+                Ok(response) => Cause::Response(response),
+                Err(err) => Cause::Error(err),
+            };
+            self.retrying(&upload_body.events, attempt, cause);
+            tokio::time::sleep(policy.delay(attempt)).await;
+            attempt += 1;
+        }
+    }
+
+    /// Sends the body once, unless the circuit breaker is open
+    async fn attempt(
+        &self,
+        upload_body: &UploadBody,
+        encoded: Option<&[u8]>,
+        attempt: u32,
+    ) -> Result<AmplitudeResponse, AmplitudeError> {
+        let breaker = match &self.breaker {
+            Some(breaker) => breaker,
+            None => return self._send(upload_body, encoded, attempt).await,
         };
-        Ok(serde_json::from_str(&amp_response)?)
+        if !breaker.allow() {
+            return Err(AmplitudeError::CircuitOpen);
+        }
+        let result = self._send(upload_body, encoded, attempt).await;
+        breaker.record(match &result {
+            Ok(response) => response.is_retryable(),
+            Err(err) => err.is_retryable(),
+        });
+        result
+    }
+
+    /// Serializes the body the way it is sent over the wire
+    fn encode(&self, upload_body: &UploadBody) -> Result<Vec<u8>, AmplitudeError> {
+        let json = serde_json::to_vec(upload_body)?;
+        #[cfg(feature = "gzip")]
+        if let Some(level) = self.gzip {
+            return Ok(crate::gzip::compress(&json, level)?);
+        }
+        Ok(json)
+    }
+
+    /// Value of the Content-Encoding header, if the body is encoded
+    fn content_encoding(&self) -> Option<&'static str> {
+        #[cfg(feature = "gzip")]
+        if self.gzip.is_some() {
+            return Some("gzip");
+        }
+        None
+    }
+
+    /// Uploads the body within a span (with the `tracing` feature). The fields of the span
+    /// are `endpoint`, `events`, `attempt`, `bytes`, `status` and `response`
+    async fn _send(
+        &self,
+        upload_body: &UploadBody,
+        encoded: Option<&[u8]>,
+        attempt: u32,
+    ) -> Result<AmplitudeResponse, AmplitudeError> {
+        #[cfg(feature = "tracing")]
+        {
+            use tracing::{field, Instrument};
+            // the url never contains the api key, which is only in the body
+            let span = tracing::info_span!(
+                "amplitude_upload",
+                endpoint = %self.url(),
+                events = upload_body.events.len(),
+                attempt,
+                bytes = field::Empty,
+                status = field::Empty,
+                response = field::Empty,
+            );
+            self.upload(upload_body, encoded).instrument(span).await
+        }
+        #[cfg(not(feature = "tracing"))]
+        {
+            let _ = attempt;
+            self.upload(upload_body, encoded).await
+        }
+    }
+
+    /// Posts the body, or the already encoded one if it is given
+    async fn upload(
+        &self,
+        upload_body: &UploadBody,
+        encoded: Option<&[u8]>,
+    ) -> Result<AmplitudeResponse, AmplitudeError> {
+        let started = Instant::now();
+        let (result, bytes) = match &self.recorder {
+            Some(recorder) => match recorder.record(upload_body) {
+                Ok((response, bytes)) => (Ok(response), bytes),
+                Err(err) => (Err(err), 0),
+            },
+            None => {
+                let body = match encoded {
+                    Some(body) => body.to_vec(),
+                    None => self.encode(upload_body)?,
+                };
+                let bytes = body.len();
+                (self.post(body).await, bytes)
+            }
+        };
+        self.metrics.request(
+            upload_body.events.len(),
+            bytes,
+            started.elapsed(),
+            result.as_ref(),
+        );
+        #[cfg(feature = "tracing")]
+        {
+            let span = tracing::Span::current();
+            span.record("bytes", bytes);
+            match &result {
+                Ok(response) => {
+                    span.record("response", response.kind());
+                    tracing::debug!("amplitude upload finished");
+                }
+                Err(err) => {
+                    span.record("response", "Error");
+                    tracing::warn!(error = %err, "amplitude upload failed");
+                }
+            }
+        }
+        result
+    }
+
+    /// Posts the encoded body and maps the HTTP response
+    async fn post(&self, body: Vec<u8>) -> Result<AmplitudeResponse, AmplitudeError> {
+        let headers = std::iter::once(("Content-Type", "application/json"))
+            .chain(
+                self.content_encoding()
+                    .map(|encoding| ("Content-Encoding", encoding)),
+            )
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect();
+        let request = Request {
+            url: self.url(),
+            headers,
+            body,
+        };
+        let post = self.transport.post(request);
+        let response = match self.timeout {
+            Some(timeout) => tokio::time::timeout(timeout, post)
+                .await
+                .map_err(|_| AmplitudeError::NetworkError("request timed out".into()))?,
+            None => post.await,
+        }
+        .map_err(AmplitudeError::NetworkError)?;
+        #[cfg(feature = "tracing")]
+        tracing::Span::current().record("status", response.status);
+        let text = String::from_utf8_lossy(&response.body).into_owned();
+        let tag = match response.status {
+            200 => Some("Ok"),
+            400 => Some("BadRequest"),
+            413 => Some("PayloadTooLarge"),
+            429 => Some("TooManyRequests"),
+            500 | 502 | 504 => Some("ServerError"),
+            503 => Some("ServiceUnavailable"),
+            _ => None,
+        };
+        if let Some(tag) = tag {
+            if let Ok(amp_response) = serde_json::from_str(&Self::add_tag(tag, text.clone())) {
+                return Ok(amp_response);
+            }
+        }
+        Ok(AmplitudeResponse::Unexpected(Unexpected::new(
+            response.status,
+            response.headers,
+            text,
+        )))
     }
 
     /// Adds enum variant's tag so serde can distinguish beetween enum variants
@@ -126,3 +913,75 @@ impl Amp {
         format!(r#"{{"{tag}": {text}}}"#, tag = tag, text = text)
     }
 }
+
+/// What happened to events given to [Amp::send](Amp::send): the report of the requests
+/// which were answered, the events of the ones which failed and the first error
+#[derive(Default)]
+struct Outcome {
+    report: Report,
+    unsent: Vec<Event>,
+    error: Option<AmplitudeError>,
+}
+
+impl Outcome {
+    fn merge(&mut self, other: Outcome) {
+        self.report.merge(other.report);
+        self.unsent.extend(other.unsent);
+        if self.error.is_none() {
+            self.error = other.error;
+        }
+    }
+
+    /// The report, or the error together with the report of the requests answered before
+    fn into_result(self) -> Result<Report, AmplitudeError> {
+        match self.error {
+            None => Ok(self.report),
+            Some(err) if self.report.attempts == 0 => Err(err),
+            Some(err) => Err(AmplitudeError::PartiallySent {
+                report: Box::new(self.report),
+                unsent: self.unsent,
+                source: Box::new(err),
+            }),
+        }
+    }
+}
+
+/// Events waiting to be sent by [Amp::send](Amp::send)
+struct Batch {
+    events: Vec<Event>,
+    /// The body as it is sent over the wire, if it was already encoded
+    encoded: Option<Vec<u8>>,
+    not_before: Option<Instant>,
+    resends: u32,
+}
+
+impl Batch {
+    fn new(events: Vec<Event>) -> Self {
+        Self {
+            events,
+            encoded: None,
+            not_before: None,
+            resends: 0,
+        }
+    }
+
+    /// A batch which sends a part of this batch instead of it
+    fn part(&self, events: Vec<Event>) -> Self {
+        Self {
+            events,
+            encoded: None,
+            not_before: None,
+            resends: self.resends,
+        }
+    }
+
+    /// A batch which sends a part of this batch once again
+    fn resend(&self, events: Vec<Event>, not_before: Option<Instant>) -> Self {
+        Self {
+            events,
+            encoded: None,
+            not_before,
+            resends: self.resends + 1,
+        }
+    }
+}
@@ -0,0 +1,93 @@
+//! A blocking client for non-async code
+//!
+//! Every call blocks the current thread until the request is done,
+//! so it must not be used from within an async runtime.
+
+use std::sync::Arc;
+
+use tokio::runtime::Runtime;
+
+use crate::entities::Event;
+use crate::report::Report;
+
+use super::*;
+
+/// The blocking counterpart of [crate::Amp](crate::Amp)
+#[derive(Clone, Debug)]
+pub struct Amp {
+    inner: crate::Amp,
+    runtime: Arc<Runtime>,
+}
+
+impl Amp {
+    pub fn from_env() -> Result<Self, AmplitudeError> {
+        let inner = crate::Amp::from_env()?;
+        Ok(Self::with_runtime(inner, Self::runtime()?))
+    }
+
+    /// # Panics
+    ///
+    /// Panics if a tokio runtime cannot be started
+    pub fn new<S>(api_key: S) -> Self
+    where
+        S: Into<crate::ApiKey>,
+    {
+        Self::from(crate::Amp::new(api_key))
+    }
+
+    /// Sets HTTP API V2 (Single) url to send request to
+    pub fn single(&mut self) -> &mut Self {
+        self.inner.single();
+        self
+    }
+
+    /// Sets batch url
+    pub fn batch(&mut self) -> &mut Self {
+        self.inner.batch();
+        self
+    }
+
+    /// Sets minimum permitted length for user_id & device_id fields
+    pub fn set_min_id_length(&mut self, length: u16) -> &mut Self {
+        self.inner.set_min_id_length(length);
+        self
+    }
+
+    /// The async client to change any other setting
+    pub fn inner_mut(&mut self) -> &mut crate::Amp {
+        &mut self.inner
+    }
+
+    /// Sends bunch of events to the amplitude servers
+    pub fn send(&self, events: Vec<Event>) -> Result<Report, AmplitudeError> {
+        self.runtime.block_on(self.inner.send(events))
+    }
+
+    /// Sends an event to the amplitude servers
+    pub fn send_one(&self, event: Event) -> Result<Report, AmplitudeError> {
+        self.send(vec![event])
+    }
+
+    fn with_runtime(inner: crate::Amp, runtime: Runtime) -> Self {
+        Self {
+            inner,
+            runtime: Arc::new(runtime),
+        }
+    }
+
+    fn runtime() -> Result<Runtime, AmplitudeError> {
+        Ok(tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()?)
+    }
+}
+
+impl From<crate::Amp> for Amp {
+    /// # Panics
+    ///
+    /// Panics if a tokio runtime cannot be started
+    fn from(inner: crate::Amp) -> Self {
+        let runtime = Self::runtime().expect("failed to start a tokio runtime");
+        Self::with_runtime(inner, runtime)
+    }
+}
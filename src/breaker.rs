@@ -0,0 +1,106 @@
+use std::sync::{Mutex, PoisonError};
+use std::time::Duration;
+
+use tokio::time::Instant;
+
+/// Describes when the circuit breaker of [Amp](crate::Amp) stops sending requests
+///
+/// Failures are the responses and errors which are
+/// [retryable](crate::response::AmplitudeResponse::is_retryable),
+/// i.e. server errors and network errors. Every other outcome counts as a success.
+#[derive(Clone, Debug)]
+pub struct BreakerPolicy {
+    /// Number of failed requests in a row which opens the circuit
+    pub failure_threshold: u32,
+    /// How long the circuit stays open before a probe request is let through
+    pub open_for: Duration,
+}
+
+impl Default for BreakerPolicy {
+    fn default() -> Self {
+        Self {
+            failure_threshold: 5,
+            open_for: Duration::from_secs(30),
+        }
+    }
+}
+
+/// State of the circuit breaker
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BreakerState {
+    /// Requests are sent
+    Closed,
+    /// Requests fail right away with [CircuitOpen](crate::AmplitudeError::CircuitOpen)
+    Open,
+    /// A single probe request is sent, its outcome closes or opens the circuit again
+    HalfOpen,
+}
+
+#[derive(Debug)]
+pub(crate) struct CircuitBreaker {
+    policy: BreakerPolicy,
+    state: Mutex<State>,
+}
+
+#[derive(Debug)]
+struct State {
+    failures: u32,
+    opened_at: Option<Instant>,
+    probing: bool,
+}
+
+impl CircuitBreaker {
+    pub fn new(policy: BreakerPolicy) -> Self {
+        Self {
+            policy,
+            state: Mutex::new(State {
+                failures: 0,
+                opened_at: None,
+                probing: false,
+            }),
+        }
+    }
+
+    /// Whether a request may be sent now. Once the circuit has been open long enough,
+    /// lets one probe through. Another probe is let through after the same time,
+    /// in case the outcome of the first one is never recorded
+    pub fn allow(&self) -> bool {
+        let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        match state.opened_at {
+            None => true,
+            Some(at) if at.elapsed() >= self.policy.open_for => {
+                state.probing = true;
+                state.opened_at = Some(Instant::now());
+                true
+            }
+            Some(_) => false,
+        }
+    }
+
+    /// Records the outcome of a request which was [allowed](CircuitBreaker::allow)
+    pub fn record(&self, failed: bool) {
+        let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        if !failed {
+            state.failures = 0;
+            state.opened_at = None;
+            state.probing = false;
+            return;
+        }
+        state.failures = state.failures.saturating_add(1);
+        if state.probing || state.failures >= self.policy.failure_threshold.max(1) {
+            state.opened_at = Some(Instant::now());
+            state.probing = false;
+        }
+    }
+
+    pub fn state(&self) -> BreakerState {
+        let state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        match state.opened_at {
+            None => BreakerState::Closed,
+            Some(at) if state.probing || at.elapsed() >= self.policy.open_for => {
+                BreakerState::HalfOpen
+            }
+            Some(_) => BreakerState::Open,
+        }
+    }
+}
@@ -0,0 +1,305 @@
+use std::env::VarError;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::entities::Event;
+use crate::key::ApiKey;
+use crate::retry::RetryPolicy;
+use crate::server::ServerZone;
+use crate::transport::Transport;
+use crate::Amp;
+
+use super::*;
+
+/// Configures an [Amp](crate::Amp) at once
+///
+/// ```no_run
+/// # fn main() -> Result<(), amplitude::AmplitudeError> {
+/// use std::time::Duration;
+/// use amplitude::{AmpBuilder, ServerZone};
+///
+/// let amp = AmpBuilder::new("api key")
+///     .batch()
+///     .server_zone(ServerZone::EU)
+///     .timeout(Duration::from_secs(10))
+///     .build()?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Debug)]
+pub struct AmpBuilder {
+    api_key: ApiKey,
+    batch: bool,
+    server_zone: ServerZone,
+    base_url: Option<String>,
+    transport: Option<Arc<dyn Transport>>,
+    timeout: Option<Duration>,
+    #[cfg(feature = "reqwest")]
+    connect_timeout: Option<Duration>,
+    min_id_length: Option<u16>,
+    retry: Option<RetryPolicy>,
+    defaults: Option<Event>,
+}
+
+impl AmpBuilder {
+    const ENV_API_KEY: &'static str = "AMPLITUDE_API_KEY";
+    const ENV_ENDPOINT: &'static str = "AMPLITUDE_ENDPOINT";
+    const ENV_SERVER_ZONE: &'static str = "AMPLITUDE_SERVER_ZONE";
+    const ENV_BASE_URL: &'static str = "AMPLITUDE_BASE_URL";
+    const ENV_TIMEOUT: &'static str = "AMPLITUDE_TIMEOUT_MS";
+    #[cfg(feature = "reqwest")]
+    const ENV_CONNECT_TIMEOUT: &'static str = "AMPLITUDE_CONNECT_TIMEOUT_MS";
+    const ENV_MIN_ID_LENGTH: &'static str = "AMPLITUDE_MIN_ID_LENGTH";
+    const ENV_MAX_ATTEMPTS: &'static str = "AMPLITUDE_MAX_ATTEMPTS";
+
+    pub fn new<S>(api_key: S) -> Self
+    where
+        S: Into<ApiKey>,
+    {
+        Self {
+            api_key: api_key.into(),
+            batch: false,
+            server_zone: ServerZone::default(),
+            base_url: None,
+            transport: None,
+            timeout: None,
+            #[cfg(feature = "reqwest")]
+            connect_timeout: None,
+            min_id_length: None,
+            retry: None,
+            defaults: None,
+        }
+    }
+
+    /// Reads the configuration from environment variables:
+    ///
+    /// - `AMPLITUDE_API_KEY` (required)
+    /// - `AMPLITUDE_ENDPOINT`: `single` (default) or `batch`
+    /// - `AMPLITUDE_SERVER_ZONE`: `US` (default) or `EU`
+    /// - `AMPLITUDE_BASE_URL`: takes precedence over the zone
+    /// - `AMPLITUDE_TIMEOUT_MS`: timeout of a request in milliseconds
+    /// - `AMPLITUDE_CONNECT_TIMEOUT_MS`: timeout of connecting in milliseconds
+    ///   (`reqwest` feature)
+    /// - `AMPLITUDE_MIN_ID_LENGTH`: minimum length of user_id and device_id
+    /// - `AMPLITUDE_MAX_ATTEMPTS`: enables retries with the
+    ///   [default policy](RetryPolicy::default) and the given number of attempts
+    ///
+    /// All missing and invalid values are reported together with
+    /// [InvalidConfig](AmplitudeError::InvalidConfig)
+    pub fn from_env() -> Result<Self, AmplitudeError> {
+        let mut errors = Vec::new();
+        let api_key = Self::var(Self::ENV_API_KEY, &mut errors);
+        match &api_key {
+            Some(api_key) if api_key.trim().is_empty() => {
+                errors.push(format!("{} is empty", Self::ENV_API_KEY))
+            }
+            Some(_) => {}
+            None if errors.is_empty() => errors.push(format!("{} is not set", Self::ENV_API_KEY)),
+            None => {}
+        }
+        let mut builder = Self::new(api_key.unwrap_or_default());
+        match Self::var(Self::ENV_ENDPOINT, &mut errors).as_deref() {
+            None => {}
+            Some(endpoint) if endpoint.eq_ignore_ascii_case("single") => {}
+            Some(endpoint) if endpoint.eq_ignore_ascii_case("batch") => {
+                builder.batch();
+            }
+            Some(endpoint) => errors.push(Self::invalid(Self::ENV_ENDPOINT, endpoint)),
+        }
+        if let Some(zone) = Self::parse(Self::ENV_SERVER_ZONE, &mut errors) {
+            builder.server_zone(zone);
+        }
+        if let Some(base_url) = Self::var(Self::ENV_BASE_URL, &mut errors) {
+            builder.base_url(base_url);
+        }
+        if let Some(millis) = Self::parse(Self::ENV_TIMEOUT, &mut errors) {
+            builder.timeout(Duration::from_millis(millis));
+        }
+        #[cfg(feature = "reqwest")]
+        if let Some(millis) = Self::parse(Self::ENV_CONNECT_TIMEOUT, &mut errors) {
+            builder.connect_timeout(Duration::from_millis(millis));
+        }
+        if let Some(length) = Self::parse(Self::ENV_MIN_ID_LENGTH, &mut errors) {
+            builder.min_id_length(length);
+        }
+        if let Some(attempts) = Self::parse(Self::ENV_MAX_ATTEMPTS, &mut errors) {
+            builder.retry_policy(RetryPolicy {
+                max_attempts: attempts,
+                ..RetryPolicy::default()
+            });
+        }
+        errors.extend(builder.validate());
+        if errors.is_empty() {
+            Ok(builder)
+        } else {
+            Err(AmplitudeError::InvalidConfig(errors))
+        }
+    }
+
+    /// Sends events to HTTP API V2 (default)
+    pub fn single(&mut self) -> &mut Self {
+        self.batch = false;
+        self
+    }
+
+    /// Sends events to Batch Event Upload API
+    pub fn batch(&mut self) -> &mut Self {
+        self.batch = true;
+        self
+    }
+
+    pub fn server_zone(&mut self, zone: ServerZone) -> &mut Self {
+        self.server_zone = zone;
+        self
+    }
+
+    /// Sends events to the url instead of the one of the server zone, e.g. to a proxy
+    pub fn base_url<S>(&mut self, base_url: S) -> &mut Self
+    where
+        S: Into<String>,
+    {
+        self.base_url = Some(base_url.into());
+        self
+    }
+
+    /// Sets an HTTP client to post requests with
+    pub fn transport<T>(&mut self, transport: T) -> &mut Self
+    where
+        T: Transport + 'static,
+    {
+        self.transport = Some(Arc::new(transport));
+        self
+    }
+
+    /// Sets a [reqwest client](https://docs.rs/reqwest/0.11/reqwest/struct.Client.html)
+    /// to post requests with
+    #[cfg(feature = "reqwest")]
+    pub fn client(&mut self, client: reqwest::Client) -> &mut Self {
+        self.transport(client)
+    }
+
+    /// See [Amp::set_timeout](Amp::set_timeout)
+    pub fn timeout(&mut self, timeout: Duration) -> &mut Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Timeout of connecting to the servers.
+    /// Applies to the default client only, not to the one set with [transport](AmpBuilder::transport)
+    #[cfg(feature = "reqwest")]
+    pub fn connect_timeout(&mut self, timeout: Duration) -> &mut Self {
+        self.connect_timeout = Some(timeout);
+        self
+    }
+
+    /// Sets minimum permitted length for user_id & device_id fields
+    pub fn min_id_length(&mut self, length: u16) -> &mut Self {
+        self.min_id_length = Some(length);
+        self
+    }
+
+    pub fn retry_policy(&mut self, policy: RetryPolicy) -> &mut Self {
+        self.retry = Some(policy);
+        self
+    }
+
+    /// See [Amp::set_default_event](Amp::set_default_event)
+    pub fn default_event(&mut self, defaults: Event) -> &mut Self {
+        self.defaults = Some(defaults);
+        self
+    }
+
+    /// Creates the client, reporting all invalid values together with
+    /// [InvalidConfig](AmplitudeError::InvalidConfig)
+    pub fn build(&self) -> Result<Amp, AmplitudeError> {
+        let mut errors = Vec::new();
+        if self.api_key.expose().trim().is_empty() {
+            errors.push("the api key is empty".to_string());
+        }
+        errors.extend(self.validate());
+        if !errors.is_empty() {
+            return Err(AmplitudeError::InvalidConfig(errors));
+        }
+        let mut amp = Amp::new(self.api_key.clone());
+        if self.batch {
+            amp.batch();
+        }
+        amp.set_server_zone(self.server_zone);
+        if let Some(base_url) = &self.base_url {
+            amp.set_base_url(base_url.clone());
+        }
+        if let Some(transport) = &self.transport {
+            amp.set_transport(transport.clone());
+        }
+        #[cfg(feature = "reqwest")]
+        if let (None, Some(timeout)) = (&self.transport, self.connect_timeout) {
+            let client = reqwest::Client::builder()
+                .connect_timeout(timeout)
+                .build()
+                .map_err(|err| AmplitudeError::InvalidConfig(vec![err.to_string()]))?;
+            amp.set_client(client);
+        }
+        if let Some(timeout) = self.timeout {
+            amp.set_timeout(timeout);
+        }
+        if let Some(length) = self.min_id_length {
+            amp.set_min_id_length(length);
+        }
+        if let Some(policy) = &self.retry {
+            amp.set_retry_policy(policy.clone());
+        }
+        if let Some(defaults) = &self.defaults {
+            amp.set_default_event(defaults.clone());
+        }
+        Ok(amp)
+    }
+
+    /// Problems with the values set, except the api key
+    fn validate(&self) -> Vec<String> {
+        let mut errors = Vec::new();
+        if let Some(base_url) = &self.base_url {
+            if !base_url.starts_with("http://") && !base_url.starts_with("https://") {
+                errors.push(format!("base url {:?} is not an http(s) url", base_url));
+            }
+        }
+        if self.timeout == Some(Duration::ZERO) {
+            errors.push("the timeout is zero".to_string());
+        }
+        if let Some(policy) = &self.retry {
+            if policy.max_attempts == 0 {
+                errors.push("the retry policy allows no attempts".to_string());
+            }
+        }
+        errors
+    }
+
+    fn var(name: &str, errors: &mut Vec<String>) -> Option<String> {
+        match std::env::var(name) {
+            Ok(value) => Some(value),
+            Err(VarError::NotPresent) => None,
+            Err(VarError::NotUnicode(_)) => {
+                errors.push(format!("{} is not valid unicode", name));
+                None
+            }
+        }
+    }
+
+    fn parse<T>(name: &str, errors: &mut Vec<String>) -> Option<T>
+    where
+        T: FromStr,
+    {
+        let value = Self::var(name, errors)?;
+        match value.trim().parse() {
+            Ok(parsed) => Some(parsed),
+            Err(_) => {
+                errors.push(Self::invalid(name, &value));
+                None
+            }
+        }
+    }
+
+    fn invalid(name: &str, value: &str) -> String {
+        format!("{} has an invalid value {:?}", name, value)
+    }
+}
@@ -0,0 +1,63 @@
+use std::fmt::{self, Debug};
+use std::sync::Arc;
+
+use crate::entities::Event;
+use crate::report::Reason;
+use crate::response::AmplitudeResponse;
+
+use super::*;
+
+/// Why [Amp](crate::Amp) failed to deliver or retries events
+#[derive(Debug, Clone, Copy)]
+pub enum Cause<'a> {
+    /// The request was answered with the response
+    Response(&'a AmplitudeResponse),
+    /// The events were rejected for the reason, e.g. after an invalid request was split
+    Rejected(&'a Reason),
+    /// The request failed with the error
+    Error(&'a AmplitudeError),
+}
+
+type Delivered = Arc<dyn Fn(&[Event], &AmplitudeResponse) + Send + Sync>;
+type Failed = Arc<dyn Fn(&[Event], Cause<'_>) + Send + Sync>;
+type Retry = Arc<dyn Fn(&[Event], u32, Cause<'_>) + Send + Sync>;
+
+/// Functions [Amp](crate::Amp) notifies of the outcomes of requests
+#[derive(Clone, Default)]
+pub(crate) struct Callbacks {
+    pub delivered: Option<Delivered>,
+    pub failed: Option<Failed>,
+    pub retry: Option<Retry>,
+}
+
+impl Callbacks {
+    pub fn delivered(&self, events: &[Event], response: &AmplitudeResponse) {
+        if let Some(callback) = &self.delivered {
+            callback(events, response);
+        }
+    }
+
+    pub fn failed(&self, events: &[Event], cause: Cause<'_>) {
+        if let Some(callback) = &self.failed {
+            if !events.is_empty() {
+                callback(events, cause);
+            }
+        }
+    }
+
+    pub fn retry(&self, events: &[Event], attempt: u32, cause: Cause<'_>) {
+        if let Some(callback) = &self.retry {
+            callback(events, attempt, cause);
+        }
+    }
+}
+
+impl Debug for Callbacks {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Callbacks")
+            .field("delivered", &self.delivered.is_some())
+            .field("failed", &self.failed.is_some())
+            .field("retry", &self.retry.is_some())
+            .finish()
+    }
+}
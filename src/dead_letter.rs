@@ -0,0 +1,111 @@
+use std::fmt::Debug;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, PoisonError};
+
+use chrono::{DateTime, Utc};
+
+use crate::entities::Event;
+use crate::report::{Reason, Report};
+use crate::Amp;
+
+use super::*;
+
+/// Why an event could not be delivered
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub enum Failure {
+    /// The amplitude servers did not accept the event
+    Rejected(Reason),
+    /// The request with the event failed, e.g. the retries were exhausted
+    Error(String),
+}
+
+/// An event which could not be delivered
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[non_exhaustive]
+pub struct DeadLetter {
+    pub time: DateTime<Utc>,
+    pub event: Event,
+    pub failure: Failure,
+}
+
+impl DeadLetter {
+    pub fn new(event: Event, failure: Failure) -> Self {
+        Self {
+            time: Utc::now(),
+            event,
+            failure,
+        }
+    }
+}
+
+/// Receives the events [Amp](crate::Amp) failed to deliver for good
+pub trait DeadLetterSink: Debug + Send + Sync {
+    fn record(&self, letters: Vec<DeadLetter>) -> Result<(), AmplitudeError>;
+}
+
+/// Appends dead letters to a file, one JSON object per line
+#[derive(Debug)]
+pub struct NdjsonSink {
+    path: PathBuf,
+    file: Mutex<File>,
+}
+
+impl NdjsonSink {
+    /// Opens the file for appending, creating it if needed
+    pub fn new<P>(path: P) -> Result<Self, AmplitudeError>
+    where
+        P: Into<PathBuf>,
+    {
+        let path = path.into();
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        Ok(Self {
+            path,
+            file: Mutex::new(file),
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl DeadLetterSink for NdjsonSink {
+    fn record(&self, letters: Vec<DeadLetter>) -> Result<(), AmplitudeError> {
+        let mut data = Vec::new();
+        for letter in &letters {
+            serde_json::to_writer(&mut data, letter)?;
+            data.push(b'\n');
+        }
+        let mut file = self.file.lock().unwrap_or_else(PoisonError::into_inner);
+        file.write_all(&data)?;
+        Ok(())
+    }
+}
+
+/// Reads the dead letters from the file, skipping the lines which are not dead letters
+pub fn read<P>(path: P) -> Result<Vec<DeadLetter>, AmplitudeError>
+where
+    P: AsRef<Path>,
+{
+    let mut letters = Vec::new();
+    for line in BufReader::new(File::open(path)?).lines() {
+        if let Ok(letter) = serde_json::from_str(&line?) {
+            letters.push(letter);
+        }
+    }
+    Ok(letters)
+}
+
+/// Sends the events from the dead letter file once again.
+///
+/// The file is left as it is. If the client records dead letters to the same file,
+/// the events failing again are appended to it, so move the file away before re-driving it
+pub async fn redrive<P>(amp: &Amp, path: P) -> Result<Report, AmplitudeError>
+where
+    P: AsRef<Path>,
+{
+    let events = read(path)?.into_iter().map(|letter| letter.event).collect();
+    amp.send(events).await
+}
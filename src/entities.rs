@@ -1,15 +1,32 @@
 use super::*;
+use crate::key::ApiKey;
 use serde_json::json;
 use std::net::{Ipv4Addr, Ipv6Addr};
 
 #[skip_serializing_none]
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub(crate) struct UploadBody {
-    pub api_key: String,
+    pub api_key: ApiKey,
     pub events: Vec<Event>,
     pub options: Option<ApiOptions>,
 }
 
+impl UploadBody {
+    /// Number of bytes the serialized body takes
+    pub fn size(&self) -> usize {
+        serde_json::to_vec(self)
+            .map(|v| v.len())
+            .unwrap_or_default()
+    }
+
+    /// Number of bytes an event adds to the serialized body (including the separating comma)
+    pub fn event_size(event: &Event) -> usize {
+        serde_json::to_vec(event)
+            .map(|v| v.len() + 1)
+            .unwrap_or_default()
+    }
+}
+
 /// Sets additional API options
 ///
 /// [The official docs](https://developers.amplitude.com/docs/http-api-v2#schemaRequestOptions)
@@ -88,6 +105,115 @@ impl Event {
     }
 }
 
+impl Event {
+    /// Takes the fields which are not set from the defaults, except the ones identifying
+    /// a single event (time, event_id and insert_id), which would make the amplitude
+    /// servers deduplicate the events
+    pub(crate) fn fill_defaults(&mut self, defaults: &Event) {
+        macro_rules! fill {
+            ($($field:ident),*) => {
+                $(
+                    if self.$field.is_none() {
+                        self.$field = defaults.$field.clone();
+                    }
+                )*
+            };
+        }
+        fill!(
+            event_type,
+            user_id,
+            device_id,
+            event_properties,
+            user_properties,
+            groups,
+            app_version,
+            platform,
+            os_name,
+            os_version,
+            device_brand,
+            device_manufacturer,
+            device_model,
+            carrier,
+            country,
+            region,
+            city,
+            dma,
+            language,
+            price,
+            quantity,
+            revenue,
+            product_id,
+            revenue_type,
+            location_lat,
+            location_lng,
+            ip,
+            idfa,
+            idfv,
+            adid,
+            android_id,
+            session_id
+        );
+    }
+}
+
+impl Event {
+    /// The user_id of the event, if set
+    pub fn get_user_id(&self) -> Option<&str> {
+        self.user_id.as_deref()
+    }
+
+    /// The device_id of the event, if set
+    pub fn get_device_id(&self) -> Option<&str> {
+        self.device_id.as_deref()
+    }
+
+    /// The event_type of the event, if set
+    pub fn get_event_type(&self) -> Option<&str> {
+        self.event_type.as_deref()
+    }
+
+    /// The insert_id of the event, if set
+    pub fn get_insert_id(&self) -> Option<&str> {
+        self.insert_id.as_deref()
+    }
+}
+
+/// How [Amp](crate::Amp) assigns an insert_id to the events which have none
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum InsertIds {
+    /// A random UUID (v4)
+    Random,
+    /// A UUID (v5) hashed from user_id, device_id, event_type, time and event_id,
+    /// so the same event gets the same id whenever it is sent.
+    /// Events which differ in none of these fields are deduplicated.
+    /// Events without the time get a random id, as they could not be told apart
+    Hashed,
+}
+
+impl Event {
+    /// Sets the insert_id unless the event has one
+    pub(crate) fn assign_insert_id(&mut self, ids: InsertIds) {
+        if self.insert_id.is_some() {
+            return;
+        }
+        let id = match ids {
+            InsertIds::Random => uuid::Uuid::new_v4(),
+            InsertIds::Hashed if self.time.is_none() => uuid::Uuid::new_v4(),
+            InsertIds::Hashed => {
+                let key = serde_json::json!([
+                    self.user_id,
+                    self.device_id,
+                    self.event_type,
+                    self.time,
+                    self.event_id,
+                ]);
+                uuid::Uuid::new_v5(&uuid::Uuid::NAMESPACE_OID, key.to_string().as_bytes())
+            }
+        };
+        self.insert_id = Some(id.to_string());
+    }
+}
+
 impl Event {
     /// A readable ID specified by you. Must have a minimum length of 5 characters.
     /// Required unless device_id is present.
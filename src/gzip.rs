@@ -0,0 +1,11 @@
+use std::io::Write;
+
+use flate2::write::GzEncoder;
+use flate2::Compression;
+
+/// Compresses the data with the given level (0 - 9)
+pub(crate) fn compress(data: &[u8], level: u32) -> std::io::Result<Vec<u8>> {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::new(level.min(9)));
+    encoder.write_all(data)?;
+    encoder.finish()
+}
@@ -0,0 +1,186 @@
+use std::fmt::{self, Debug, Display};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, PoisonError, RwLock};
+use std::time::Duration;
+
+use tokio::runtime::Handle;
+
+use super::*;
+
+/// An api key which does not show up in logs: `Debug` and `Display` print `***`.
+/// Clones share the same string
+#[derive(Clone, PartialEq, Eq)]
+pub struct ApiKey(Arc<str>);
+
+impl ApiKey {
+    pub fn new<S>(key: S) -> Self
+    where
+        S: Into<String>,
+    {
+        Self(key.into().into())
+    }
+
+    /// Reads the key from the file, ignoring the surrounding whitespace
+    pub fn from_file<P>(path: P) -> Result<Self, AmplitudeError>
+    where
+        P: AsRef<Path>,
+    {
+        Ok(Self::new(std::fs::read_to_string(path)?.trim()))
+    }
+
+    /// The key itself
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Debug for ApiKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("ApiKey(***)")
+    }
+}
+
+impl Display for ApiKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("***")
+    }
+}
+
+impl From<String> for ApiKey {
+    fn from(key: String) -> Self {
+        Self::new(key)
+    }
+}
+
+impl From<&String> for ApiKey {
+    fn from(key: &String) -> Self {
+        Self::new(key.as_str())
+    }
+}
+
+impl From<&str> for ApiKey {
+    fn from(key: &str) -> Self {
+        Self::new(key)
+    }
+}
+
+impl Serialize for ApiKey {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.serialize_str(self.expose())
+    }
+}
+
+impl<'de> Deserialize<'de> for ApiKey {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        String::deserialize(deserializer).map(Self::new)
+    }
+}
+
+type Fetch = Box<dyn Fn() -> Result<ApiKey, AmplitudeError> + Send + Sync>;
+
+/// The current api key of [Amp](crate::Amp), shared by its clones
+pub(crate) struct KeyStore {
+    key: Arc<RwLock<ApiKey>>,
+    provider: Option<Provider>,
+}
+
+struct Provider {
+    fetch: Arc<Fetch>,
+    refresh: Duration,
+    started: AtomicBool,
+}
+
+impl KeyStore {
+    pub fn new(key: ApiKey) -> Self {
+        Self {
+            key: Arc::new(RwLock::new(key)),
+            provider: None,
+        }
+    }
+
+    /// A store which asks the function for the key now and then once the refresh
+    /// interval passes
+    pub fn with_provider(fetch: Fetch, refresh: Duration) -> Result<Self, AmplitudeError> {
+        let store = Self {
+            key: Arc::new(RwLock::new(fetch()?)),
+            provider: Some(Provider {
+                fetch: Arc::new(fetch),
+                refresh,
+                started: AtomicBool::new(false),
+            }),
+        };
+        store.start();
+        Ok(store)
+    }
+
+    /// The key to send now
+    pub fn current(&self) -> ApiKey {
+        self.start();
+        self.key
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    pub fn set(&self, key: ApiKey) {
+        *self.key.write().unwrap_or_else(PoisonError::into_inner) = key;
+    }
+
+    /// Starts refreshing the key in the background, once there is a runtime to do it.
+    /// The function runs on a blocking thread, a failure to fetch a new key keeps the old one
+    fn start(&self) {
+        let provider = match &self.provider {
+            Some(provider) => provider,
+            None => return,
+        };
+        if provider.started.load(Ordering::Acquire) {
+            return;
+        }
+        let handle = match Handle::try_current() {
+            Ok(handle) => handle,
+            Err(_) => return,
+        };
+        if provider.started.swap(true, Ordering::AcqRel) {
+            return;
+        }
+        let fetch = provider.fetch.clone();
+        let refresh = provider.refresh;
+        let key = Arc::downgrade(&self.key);
+        handle.spawn(async move {
+            loop {
+                tokio::time::sleep(refresh).await;
+                if key.strong_count() == 0 {
+                    break;
+                }
+                let fetch = fetch.clone();
+                let fetched = match tokio::task::spawn_blocking(move || fetch()).await {
+                    Ok(Ok(fetched)) => fetched,
+                    _ => continue,
+                };
+                match key.upgrade() {
+                    Some(key) => *key.write().unwrap_or_else(PoisonError::into_inner) = fetched,
+                    None => break,
+                }
+            }
+        });
+    }
+}
+
+impl Debug for KeyStore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("KeyStore")
+            .field(
+                "key",
+                &*self.key.read().unwrap_or_else(PoisonError::into_inner),
+            )
+            .field("provider", &self.provider.is_some())
+            .finish()
+    }
+}
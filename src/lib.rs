@@ -1,10 +1,44 @@
 pub mod amp;
+#[cfg(feature = "blocking")]
+pub mod blocking;
+pub mod breaker;
+pub mod builder;
+pub mod callbacks;
+pub mod dead_letter;
 pub mod entities;
+#[cfg(feature = "gzip")]
+mod gzip;
+pub mod key;
+pub mod limiter;
 pub(crate) mod prelude;
+pub mod persistent;
+pub mod queue;
+pub mod recorder;
+pub mod report;
 pub mod response;
+pub mod retry;
+pub mod router;
+pub mod server;
+pub mod stats;
+pub mod transport;
 
-pub use amp::Amp;
-pub use entities::Event;
+pub use amp::{Amp, Limits};
+pub use breaker::{BreakerPolicy, BreakerState};
+pub use builder::AmpBuilder;
+pub use callbacks::Cause;
+pub use dead_letter::{DeadLetterSink, NdjsonSink};
+pub use entities::{Event, InsertIds};
+pub use key::ApiKey;
+pub use limiter::{Rate, RateLimits};
+pub use persistent::{DiskQueueConfig, PersistentQueue};
+pub use queue::{AmpQueue, QueueConfig, ShutdownSummary};
+pub use recorder::Recorder;
+pub use report::{Reason, Rejected, Report};
+pub use retry::{RetryPolicy, ThrottlePolicy};
+pub use router::{AmpRouter, RouterReport};
+pub use server::ServerZone;
+pub use stats::Stats;
+pub use transport::Transport;
 use prelude::*;
 use thiserror::Error;
 
@@ -16,11 +50,48 @@ pub enum AmplitudeError {
     InitializationError(String),
 
     #[error("A network error: {0}")]
-    NetworkError(#[from] reqwest::Error),
+    NetworkError(#[source] transport::TransportError),
 
     #[error("Serde error")]
     SerdeError(#[from] serde_json::Error),
 
+    #[error("IO error: {0}")]
+    IoError(#[from] std::io::Error),
+
+    #[error("invalid configuration: {}", .0.join("; "))]
+    InvalidConfig(Vec<String>),
+
+    #[error("circuit breaker is open, the request was not sent")]
+    CircuitOpen,
+
+    #[error("queue is closed")]
+    QueueClosed,
+
+    #[error("queue is full")]
+    QueueFull,
+
+    #[error("gave up after {attempts} attempts: {source}")]
+    RetriesExhausted {
+        attempts: u32,
+        source: Box<AmplitudeError>,
+    },
+
+    #[error("{} events were not sent: {source}", .unsent.len())]
+    PartiallySent {
+        /// The outcome of the requests which were answered
+        report: Box<Report>,
+        /// Events of the requests which failed
+        unsent: Vec<Event>,
+        source: Box<AmplitudeError>,
+    },
+
     #[error("unknown error")]
     UnknownError,
 }
+
+impl AmplitudeError {
+    /// Whether the failed request may succeed if it is sent again later
+    pub fn is_retryable(&self) -> bool {
+        matches!(self, AmplitudeError::NetworkError(_))
+    }
+}
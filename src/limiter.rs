@@ -0,0 +1,169 @@
+use std::sync::{Mutex, PoisonError};
+use std::time::Duration;
+
+use tokio::time::Instant;
+
+use crate::entities::Event;
+
+use super::*;
+
+/// A sustained rate of events with an allowed burst
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Rate {
+    pub events_per_second: f64,
+    /// How many events may be sent at once after a period of inactivity
+    pub burst: u32,
+}
+
+impl Rate {
+    /// The rate with a burst of one second worth of events
+    pub fn per_second(events: u32) -> Self {
+        Self {
+            events_per_second: f64::from(events.max(1)),
+            burst: events.max(1),
+        }
+    }
+}
+
+/// Limits [Amp](crate::Amp) keeps to, so the amplitude servers do not throttle it
+///
+/// [The official docs](https://developers.amplitude.com/docs/http-api-v2#upload-limit)
+#[derive(Clone, Debug)]
+pub struct RateLimits {
+    /// Limit for the events of every device_id
+    pub per_device: Option<Rate>,
+    /// Limit for the events of every user_id
+    pub per_user: Option<Rate>,
+    /// Limit for all events
+    pub global: Option<Rate>,
+}
+
+impl Default for RateLimits {
+    fn default() -> Self {
+        Self {
+            per_device: Some(Rate::per_second(30)),
+            per_user: Some(Rate::per_second(30)),
+            global: None,
+        }
+    }
+}
+
+/// What the rate limiter has done so far
+#[derive(Clone, Debug, Default)]
+#[non_exhaustive]
+pub struct LimiterStats {
+    /// Number of events which could not be sent right away
+    pub delayed_events: u64,
+    /// Sum of the delays of all events
+    pub total_delay: Duration,
+    /// Number of devices whose rate is being tracked
+    pub devices: usize,
+    /// Number of users whose rate is being tracked
+    pub users: usize,
+}
+
+/// Token buckets (in the GCRA form) of devices, users and all events together
+#[derive(Debug)]
+pub(crate) struct RateLimiter {
+    limits: RateLimits,
+    state: Mutex<State>,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    devices: HashMap<String, Instant>,
+    users: HashMap<String, Instant>,
+    global: Option<Instant>,
+    stats: LimiterStats,
+}
+
+impl RateLimiter {
+    /// Buckets are forgotten once they are idle and there are more of them than this
+    const MAX_IDLE_BUCKETS: usize = 10_000;
+
+    pub fn new(limits: RateLimits) -> Self {
+        Self {
+            limits,
+            state: Mutex::default(),
+        }
+    }
+
+    /// Reserves a slot for every event. Returns groups of events which may be sent
+    /// at the given moments, the earliest first
+    pub fn schedule(&self, events: Vec<Event>) -> Vec<(Instant, Vec<Event>)> {
+        let now = Instant::now();
+        let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        let mut waves: Vec<(Instant, Vec<Event>)> = Vec::new();
+        for event in events {
+            let device = event.get_device_id().map(String::from);
+            let user = event.get_user_id().map(String::from);
+            let State {
+                devices,
+                users,
+                global,
+                stats,
+            } = &mut *state;
+            let mut buckets = Vec::new();
+            if let (Some(rate), Some(device)) = (self.limits.per_device, device) {
+                buckets.push((rate, devices.entry(device).or_insert(now)));
+            }
+            if let (Some(rate), Some(user)) = (self.limits.per_user, user) {
+                buckets.push((rate, users.entry(user).or_insert(now)));
+            }
+            if let Some(rate) = self.limits.global {
+                buckets.push((rate, global.get_or_insert(now)));
+            }
+            let ready = buckets
+                .iter()
+                .map(|(rate, tat)| Self::ready(rate, **tat))
+                .fold(now, Instant::max);
+            for (rate, tat) in buckets {
+                *tat = (*tat).max(ready) + Self::interval(&rate);
+            }
+            if ready > now {
+                stats.delayed_events += 1;
+                stats.total_delay += ready - now;
+            }
+            // events are grouped in 100 ms windows, so a backlog does not turn into
+            // a request per event
+            let delay = ((ready - now).as_millis() as u64).div_ceil(100) * 100;
+            let at = now + Duration::from_millis(delay);
+            match waves.iter_mut().find(|(wave, _)| *wave == at) {
+                Some((_, wave)) => wave.push(event),
+                None => waves.push((at, vec![event])),
+            }
+        }
+        Self::forget_idle(&mut state, now);
+        waves.sort_by_key(|(at, _)| *at);
+        waves
+    }
+
+    pub fn stats(&self) -> LimiterStats {
+        let state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        LimiterStats {
+            devices: state.devices.len(),
+            users: state.users.len(),
+            ..state.stats.clone()
+        }
+    }
+
+    fn interval(rate: &Rate) -> Duration {
+        // a rate under one event in 1000 seconds is treated as such
+        Duration::from_secs_f64(1.0 / rate.events_per_second.max(0.001))
+    }
+
+    /// The earliest moment an event fits into the bucket with the theoretical arrival time
+    fn ready(rate: &Rate, tat: Instant) -> Instant {
+        let tolerance = Self::interval(rate) * rate.burst.saturating_sub(1);
+        tat.checked_sub(tolerance).unwrap_or(tat)
+    }
+
+    fn forget_idle(state: &mut State, now: Instant) {
+        if state.devices.len() > Self::MAX_IDLE_BUCKETS {
+            state.devices.retain(|_, tat| *tat > now);
+        }
+        if state.users.len() > Self::MAX_IDLE_BUCKETS {
+            state.users.retain(|_, tat| *tat > now);
+        }
+    }
+}
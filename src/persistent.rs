@@ -0,0 +1,428 @@
+//! A durable queue of events which survives restarts
+//!
+//! Events are appended to segment files (one serialized event per line) and removed
+//! only after [Amp](crate::Amp) got a final answer for them. The position of the first
+//! unacknowledged event is kept in a cursor file, so the events which were not sent
+//! before a crash are sent again on the next start.
+
+use std::collections::VecDeque;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::time::Duration;
+
+use tokio::sync::{mpsc, oneshot};
+use tokio::task::JoinHandle;
+
+use crate::entities::{Event, InsertIds};
+use crate::report::Report;
+use crate::response::AmplitudeResponse;
+use crate::stats::Metrics;
+use crate::Amp;
+
+use super::*;
+
+/// Settings of a [DiskQueue](DiskQueue) and its background sender
+#[derive(Clone, Debug)]
+pub struct DiskQueueConfig {
+    /// Directory keeping the segment files and the cursor
+    pub dir: PathBuf,
+    /// A new segment file is started once the current one reaches this size
+    pub max_segment_bytes: u64,
+    /// Events are refused once the segment files take this many bytes
+    pub max_total_bytes: u64,
+    /// Flushes every write to the disk, so events survive a power loss and not only a crash
+    pub sync: bool,
+    /// Maximum number of events sent at once
+    pub batch_size: usize,
+    /// How often pending events are sent, though not more than once a millisecond
+    pub interval: Duration,
+}
+
+impl DiskQueueConfig {
+    pub fn new<P>(dir: P) -> Self
+    where
+        P: Into<PathBuf>,
+    {
+        Self {
+            dir: dir.into(),
+            max_segment_bytes: 8 * 1024 * 1024,
+            max_total_bytes: 256 * 1024 * 1024,
+            sync: false,
+            batch_size: 1000,
+            interval: Duration::from_secs(5),
+        }
+    }
+}
+
+/// Position of an event in the segment files
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Cursor {
+    segment: u64,
+    offset: u64,
+}
+
+/// Append-only segment files of events with an acknowledgement cursor
+#[derive(Debug)]
+pub struct DiskQueue {
+    dir: PathBuf,
+    max_segment_bytes: u64,
+    max_total_bytes: u64,
+    sync: bool,
+    /// Ids and sizes of the segment files, oldest first. Never empty
+    segments: VecDeque<(u64, u64)>,
+    writer: File,
+    cursor: Cursor,
+}
+
+impl DiskQueue {
+    const CURSOR: &'static str = "cursor";
+    const EXTENSION: &'static str = "events";
+
+    /// Opens the queue in the directory, creating it if needed.
+    ///
+    /// A partially written last event and unreadable lines are skipped,
+    /// an unreadable cursor makes all events be sent again.
+    pub fn open(config: &DiskQueueConfig) -> Result<Self, AmplitudeError> {
+        let dir = config.dir.clone();
+        fs::create_dir_all(&dir)?;
+        let mut segments = VecDeque::new();
+        for id in Self::segment_ids(&dir)? {
+            segments.push_back((id, fs::metadata(Self::segment_path(&dir, id))?.len()));
+        }
+        if let Some((id, size)) = segments.back_mut() {
+            *size = Self::truncate_torn_write(&Self::segment_path(&dir, *id))?;
+        }
+        let cursor = Self::read_cursor(&dir)
+            .filter(|cursor| {
+                segments
+                    .iter()
+                    .any(|&(id, size)| id == cursor.segment && cursor.offset <= size)
+            })
+            .unwrap_or_else(|| Cursor {
+                segment: segments.front().map_or(0, |&(id, _)| id),
+                offset: 0,
+            });
+        if segments.is_empty() {
+            segments.push_back((cursor.segment, 0));
+        }
+        let last = segments.back().map_or(0, |&(id, _)| id);
+        let writer = Self::open_segment(&dir, last)?;
+        let mut queue = Self {
+            dir,
+            max_segment_bytes: config.max_segment_bytes,
+            max_total_bytes: config.max_total_bytes,
+            sync: config.sync,
+            segments,
+            writer,
+            cursor,
+        };
+        queue.remove_acknowledged()?;
+        Ok(queue)
+    }
+
+    /// Appends the event to the queue
+    pub fn push(&mut self, event: &Event) -> Result<(), AmplitudeError> {
+        let mut line = serde_json::to_vec(event)?;
+        line.push(b'\n');
+        let len = line.len() as u64;
+        if self.total_bytes() + len > self.max_total_bytes {
+            return Err(AmplitudeError::QueueFull);
+        }
+        let (id, size) = *self.segments.back().expect("there is always a segment");
+        if size > 0 && size + len > self.max_segment_bytes {
+            self.writer = Self::open_segment(&self.dir, id + 1)?;
+            self.segments.push_back((id + 1, 0));
+        }
+        self.writer.write_all(&line)?;
+        if self.sync {
+            self.writer.sync_data()?;
+        }
+        if let Some((_, size)) = self.segments.back_mut() {
+            *size += len;
+        }
+        Ok(())
+    }
+
+    /// Reads up to `max` unacknowledged events. Returns them together with the cursor
+    /// to [acknowledge](DiskQueue::ack) once they are sent
+    pub fn peek(&self, max: usize) -> Result<(Vec<Event>, Cursor), AmplitudeError> {
+        let mut events = Vec::new();
+        let cursor = self.read(max, |event| events.push(event))?;
+        Ok((events, cursor))
+    }
+
+    /// Number of unacknowledged events
+    pub fn pending(&self) -> Result<usize, AmplitudeError> {
+        let mut count = 0;
+        self.read(usize::MAX, |_| count += 1)?;
+        Ok(count)
+    }
+
+    /// Passes up to `max` unacknowledged events to `visit`.
+    /// Returns the cursor after the last of them
+    fn read<F>(&self, max: usize, mut visit: F) -> Result<Cursor, AmplitudeError>
+    where
+        F: FnMut(Event),
+    {
+        let mut read = 0;
+        let mut cursor = self.cursor;
+        let first = self.cursor.segment;
+        for &(id, size) in self.segments.iter().filter(|(id, _)| *id >= first) {
+            if read >= max {
+                break;
+            }
+            if id > cursor.segment {
+                cursor = Cursor {
+                    segment: id,
+                    offset: 0,
+                };
+            }
+            if cursor.offset >= size {
+                continue;
+            }
+            let mut reader = BufReader::new(File::open(Self::segment_path(&self.dir, id))?);
+            reader.seek(SeekFrom::Start(cursor.offset))?;
+            let mut line = Vec::new();
+            while read < max && cursor.offset < size {
+                line.clear();
+                let n = reader.read_until(b'\n', &mut line)?;
+                if n == 0 || !line.ends_with(b"\n") {
+                    break;
+                }
+                cursor.offset += n as u64;
+                // a corrupted line can not be sent anyway, it is acknowledged with the rest
+                if let Ok(event) = serde_json::from_slice(&line) {
+                    visit(event);
+                    read += 1;
+                }
+            }
+        }
+        Ok(cursor)
+    }
+
+    /// Marks every event before the cursor as sent
+    pub fn ack(&mut self, cursor: Cursor) -> Result<(), AmplitudeError> {
+        if cursor == self.cursor {
+            return Ok(());
+        }
+        self.cursor = cursor;
+        let tmp = self.dir.join(format!("{}.tmp", Self::CURSOR));
+        fs::write(&tmp, format!("{} {}\n", cursor.segment, cursor.offset))?;
+        fs::rename(&tmp, self.dir.join(Self::CURSOR))?;
+        self.remove_acknowledged()
+    }
+
+    /// Number of bytes the segment files take
+    pub fn total_bytes(&self) -> u64 {
+        self.segments.iter().map(|(_, size)| size).sum()
+    }
+
+    fn remove_acknowledged(&mut self) -> Result<(), AmplitudeError> {
+        while self.segments.len() > 1 && self.segments[0].0 < self.cursor.segment {
+            let (id, _) = self.segments.pop_front().expect("checked above");
+            fs::remove_file(Self::segment_path(&self.dir, id))?;
+        }
+        Ok(())
+    }
+
+    fn segment_ids(dir: &Path) -> io::Result<Vec<u64>> {
+        let mut ids = Vec::new();
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path.extension() == Some(Self::EXTENSION.as_ref()) {
+                if let Some(id) = path.file_stem().and_then(|s| s.to_str()?.parse().ok()) {
+                    ids.push(id);
+                }
+            }
+        }
+        ids.sort_unstable();
+        Ok(ids)
+    }
+
+    fn segment_path(dir: &Path, id: u64) -> PathBuf {
+        dir.join(format!("{:020}.{}", id, Self::EXTENSION))
+    }
+
+    fn open_segment(dir: &Path, id: u64) -> io::Result<File> {
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(Self::segment_path(dir, id))
+    }
+
+    fn read_cursor(dir: &Path) -> Option<Cursor> {
+        let text = fs::read_to_string(dir.join(Self::CURSOR)).ok()?;
+        let mut parts = text.split_whitespace().map(|part| part.parse().ok());
+        Some(Cursor {
+            segment: parts.next()??,
+            offset: parts.next()??,
+        })
+    }
+
+    /// Cuts off the last line if the process died while writing it. Returns the new size
+    fn truncate_torn_write(path: &Path) -> io::Result<u64> {
+        let data = fs::read(path)?;
+        let size = data
+            .iter()
+            .rposition(|byte| *byte == b'\n')
+            .map_or(0, |pos| pos + 1) as u64;
+        if size != data.len() as u64 {
+            OpenOptions::new().write(true).open(path)?.set_len(size)?;
+        }
+        Ok(size)
+    }
+}
+
+enum Command {
+    Wake,
+    Flush(oneshot::Sender<Result<bool, AmplitudeError>>),
+}
+
+/// Keeps events in a [DiskQueue](DiskQueue) and sends them with [Amp](Amp) in the background
+///
+/// Events are acknowledged once the amplitude servers gave a final answer for them,
+/// i.e. they were accepted or rejected for a reason which retrying does not fix.
+/// A rejected api key is not such a reason, the events wait for a valid one.
+/// Events without an insert_id get one before they are written to the disk
+/// ([random](InsertIds::Random) unless the client [sets](Amp::set_insert_ids) the kind),
+/// so the amplitude servers deduplicate the events which are sent again.
+/// Must be created within a tokio runtime.
+#[derive(Debug)]
+pub struct PersistentQueue {
+    storage: Arc<Mutex<DiskQueue>>,
+    sender: mpsc::UnboundedSender<Command>,
+    worker: JoinHandle<()>,
+    batch_size: usize,
+    since_wake: AtomicUsize,
+    metrics: Arc<Metrics>,
+    insert_ids: InsertIds,
+}
+
+impl PersistentQueue {
+    /// Opens the queue and starts sending the events left from the previous run
+    pub fn open(mut amp: Amp, config: DiskQueueConfig) -> Result<Self, AmplitudeError> {
+        // the events which exhausted the retries stay on the disk and are sent again
+        amp.dead_letter_exhausted(false);
+        let storage = DiskQueue::open(&config)?;
+        let metrics = amp.metrics().clone();
+        // the events left from the previous run are dequeued once they are sent
+        metrics.enqueued(storage.pending()?);
+        let storage = Arc::new(Mutex::new(storage));
+        let insert_ids = amp.insert_ids().unwrap_or(InsertIds::Random);
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let worker = Worker {
+            amp,
+            storage: storage.clone(),
+            batch_size: config.batch_size.max(1),
+        };
+        let worker = tokio::spawn(worker.run(config.interval, receiver));
+        Ok(Self {
+            storage,
+            sender,
+            worker,
+            batch_size: config.batch_size.max(1),
+            since_wake: AtomicUsize::new(0),
+            metrics,
+            insert_ids,
+        })
+    }
+
+    /// Writes the event to the disk. It will be sent in the background
+    pub fn track(&self, mut event: Event) -> Result<(), AmplitudeError> {
+        if self.sender.is_closed() {
+            return Err(AmplitudeError::QueueClosed);
+        }
+        // the id is kept on the disk, so the events sent again get the same one
+        event.assign_insert_id(self.insert_ids);
+        lock(&self.storage).push(&event)?;
+        self.metrics.enqueued(1);
+        if self.since_wake.fetch_add(1, Ordering::Relaxed) + 1 >= self.batch_size {
+            self.since_wake.store(0, Ordering::Relaxed);
+            let _ = self.sender.send(Command::Wake);
+        }
+        Ok(())
+    }
+
+    /// Tries to send all pending events. Returns whether the queue is empty afterwards
+    pub async fn flush(&self) -> Result<bool, AmplitudeError> {
+        let (done, wait) = oneshot::channel();
+        self.sender
+            .send(Command::Flush(done))
+            .map_err(|_| AmplitudeError::QueueClosed)?;
+        wait.await.map_err(|_| AmplitudeError::QueueClosed)?
+    }
+
+    /// Tries to send all pending events once more and stops the background sender.
+    /// Events which were not sent stay on the disk
+    pub async fn shutdown(self) -> Result<(), AmplitudeError> {
+        let Self { sender, worker, .. } = self;
+        drop(sender);
+        worker.await.map_err(|_| AmplitudeError::QueueClosed)
+    }
+}
+
+struct Worker {
+    amp: Amp,
+    storage: Arc<Mutex<DiskQueue>>,
+    batch_size: usize,
+}
+
+impl Worker {
+    async fn run(self, interval: Duration, mut receiver: mpsc::UnboundedReceiver<Command>) {
+        let mut ticker = tokio::time::interval(interval.max(Duration::from_millis(1)));
+        ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        loop {
+            tokio::select! {
+                command = receiver.recv() => match command {
+                    Some(Command::Wake) => {
+                        let _ = self.drain().await;
+                    }
+                    Some(Command::Flush(done)) => {
+                        let _ = done.send(self.drain().await);
+                    }
+                    None => {
+                        let _ = self.drain().await;
+                        break;
+                    }
+                },
+                _ = ticker.tick() => {
+                    let _ = self.drain().await;
+                }
+            }
+        }
+    }
+
+    /// Sends events until the queue is empty or a batch has to be retried later.
+    /// Returns whether the queue is empty
+    async fn drain(&self) -> Result<bool, AmplitudeError> {
+        loop {
+            let (events, cursor) = lock(&self.storage).peek(self.batch_size)?;
+            if events.is_empty() {
+                lock(&self.storage).ack(cursor)?;
+                return Ok(true);
+            }
+            let count = events.len();
+            let report = self.amp.send(events).await?;
+            if report.is_retryable() || Self::unauthorized(&report) {
+                return Ok(false);
+            }
+            lock(&self.storage).ack(cursor)?;
+            self.amp.metrics().dequeued(count);
+        }
+    }
+
+    /// Whether the api key was rejected, which a new key fixes rather than dropping the events
+    fn unauthorized(report: &Report) -> bool {
+        report
+            .responses
+            .iter()
+            .any(AmplitudeResponse::is_unauthorized)
+    }
+}
+
+fn lock(storage: &Mutex<DiskQueue>) -> MutexGuard<'_, DiskQueue> {
+    storage.lock().unwrap_or_else(PoisonError::into_inner)
+}
@@ -0,0 +1,298 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use tokio::sync::{mpsc, oneshot, watch};
+use tokio::time::Instant;
+
+use crate::entities::{Event, UploadBody};
+use crate::report::Report;
+use crate::Amp;
+
+use super::*;
+
+/// Limits which make [AmpQueue](AmpQueue) flush the accumulated events
+#[derive(Clone, Debug)]
+pub struct QueueConfig {
+    /// Flush as soon as this many events are accumulated
+    pub max_events: usize,
+    /// Flush as soon as the serialized events take this many bytes
+    pub max_bytes: usize,
+    /// Flush at least this often, though not more than once a millisecond
+    pub interval: Duration,
+}
+
+impl Default for QueueConfig {
+    fn default() -> Self {
+        Self {
+            max_events: 1000,
+            max_bytes: 512 * 1024,
+            interval: Duration::from_secs(10),
+        }
+    }
+}
+
+/// The outcome of the final flush made by [AmpQueue::shutdown](AmpQueue::shutdown)
+///
+/// Earlier flushes are not included, unless the shutdown deadline cut one off.
+/// Their outcome is reported by the [callbacks](Amp::on_failed),
+/// the [dead letter sink](Amp::set_dead_letter_sink) and the [stats](Amp::stats) of the client
+#[derive(Debug, Clone, Default)]
+#[non_exhaustive]
+pub struct ShutdownSummary {
+    /// Number of events accepted by the amplitude servers
+    pub delivered: usize,
+    /// Events which were rejected, failed to be sent or did not make it before the deadline
+    pub undelivered: Vec<Event>,
+}
+
+enum Command {
+    Track(Box<Event>),
+    Flush(oneshot::Sender<Result<Report, AmplitudeError>>),
+    Shutdown(Option<Instant>, oneshot::Sender<ShutdownSummary>),
+}
+
+/// Accumulates events in the background and sends them in batches with [Amp](Amp)
+///
+/// Must be created within a tokio runtime.
+/// Dropping the queue sends the pending events in the background.
+#[derive(Debug)]
+pub struct AmpQueue {
+    sender: mpsc::UnboundedSender<Command>,
+    closed: AtomicBool,
+    /// Deadline of the shutdown, which cuts off the flush in progress
+    deadline: watch::Sender<Option<Instant>>,
+}
+
+impl AmpQueue {
+    /// Spawns a background worker sending events with the given client
+    pub fn new(amp: Amp, config: QueueConfig) -> Self {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let (deadline, stop) = watch::channel(None);
+        tokio::spawn(Worker::new(amp, config, stop).run(receiver));
+        Self {
+            sender,
+            closed: AtomicBool::new(false),
+            deadline,
+        }
+    }
+
+    /// Puts an event into the queue. It will be sent with the next flush
+    pub fn track(&self, event: Event) -> Result<(), AmplitudeError> {
+        if self.closed.load(Ordering::Acquire) {
+            return Err(AmplitudeError::QueueClosed);
+        }
+        self.sender
+            .send(Command::Track(Box::new(event)))
+            .map_err(|_| AmplitudeError::QueueClosed)
+    }
+
+    /// Sends all events tracked so far and waits until it is done.
+    /// Returns the outcome the same way [Amp::send](Amp::send) does,
+    /// an empty report if there was nothing to send
+    pub async fn flush(&self) -> Result<Report, AmplitudeError> {
+        let (done, wait) = oneshot::channel();
+        self.sender
+            .send(Command::Flush(done))
+            .map_err(|_| AmplitudeError::QueueClosed)?;
+        wait.await.map_err(|_| AmplitudeError::QueueClosed)?
+    }
+
+    /// Stops accepting events, sends all pending ones and stops the background worker
+    pub async fn shutdown(&self) -> Result<ShutdownSummary, AmplitudeError> {
+        self.close(None).await
+    }
+
+    /// Same as [shutdown](AmpQueue::shutdown), but gives up sending after the timeout,
+    /// also the flush which was in progress when it was called.
+    /// Events which were being sent at that moment are reported as undelivered,
+    /// though some of them might have reached the amplitude servers
+    pub async fn shutdown_timeout(
+        &self,
+        timeout: Duration,
+    ) -> Result<ShutdownSummary, AmplitudeError> {
+        self.close(Some(Instant::now() + timeout)).await
+    }
+
+    /// Waits for Ctrl-C (or SIGTERM on unix) and then
+    /// [shuts the queue down](AmpQueue::shutdown_timeout)
+    #[cfg(feature = "signal")]
+    pub async fn shutdown_on_signal(
+        &self,
+        timeout: Duration,
+    ) -> Result<ShutdownSummary, AmplitudeError> {
+        #[cfg(unix)]
+        {
+            use tokio::signal::unix::{signal, SignalKind};
+            let mut terminate = signal(SignalKind::terminate())?;
+            tokio::select! {
+                result = tokio::signal::ctrl_c() => result?,
+                _ = terminate.recv() => {}
+            }
+        }
+        #[cfg(not(unix))]
+        tokio::signal::ctrl_c().await?;
+        self.shutdown_timeout(timeout).await
+    }
+
+    async fn close(&self, deadline: Option<Instant>) -> Result<ShutdownSummary, AmplitudeError> {
+        if self.closed.swap(true, Ordering::AcqRel) {
+            return Err(AmplitudeError::QueueClosed);
+        }
+        if deadline.is_some() {
+            let _ = self.deadline.send(deadline);
+        }
+        let (done, wait) = oneshot::channel();
+        self.sender
+            .send(Command::Shutdown(deadline, done))
+            .map_err(|_| AmplitudeError::QueueClosed)?;
+        wait.await.map_err(|_| AmplitudeError::QueueClosed)
+    }
+}
+
+struct Worker {
+    amp: Amp,
+    config: QueueConfig,
+    events: Vec<Event>,
+    bytes: usize,
+    stop: watch::Receiver<Option<Instant>>,
+    /// Events of the flush which was cut off by the shutdown deadline
+    cut_off: Vec<Event>,
+}
+
+impl Worker {
+    fn new(amp: Amp, config: QueueConfig, stop: watch::Receiver<Option<Instant>>) -> Self {
+        Self {
+            amp,
+            config,
+            events: Vec::new(),
+            bytes: 0,
+            stop,
+            cut_off: Vec::new(),
+        }
+    }
+
+    async fn run(mut self, mut receiver: mpsc::UnboundedReceiver<Command>) {
+        let mut ticker = tokio::time::interval(self.config.interval.max(Duration::from_millis(1)));
+        ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        loop {
+            tokio::select! {
+                command = receiver.recv() => match command {
+                    Some(Command::Track(event)) => {
+                        self.push(*event);
+                        if self.events.len() >= self.config.max_events
+                            || self.bytes >= self.config.max_bytes
+                        {
+                            let _ = self.flush().await;
+                        }
+                    }
+                    Some(Command::Flush(done)) => {
+                        let _ = done.send(self.flush().await);
+                    }
+                    Some(Command::Shutdown(deadline, done)) => {
+                        // events tracked while the queue was being closed
+                        receiver.close();
+                        while let Ok(command) = receiver.try_recv() {
+                            if let Command::Track(event) = command {
+                                self.push(*event);
+                            }
+                        }
+                        let _ = done.send(self.finish(deadline).await);
+                        break;
+                    }
+                    None => {
+                        let _ = self.flush().await;
+                        break;
+                    }
+                },
+                _ = ticker.tick() => {
+                    let _ = self.flush().await;
+                }
+            }
+        }
+    }
+
+    fn push(&mut self, event: Event) {
+        self.amp.metrics().enqueued(1);
+        self.bytes += UploadBody::event_size(&event);
+        self.events.push(event);
+    }
+
+    /// Sends the pending events. Events cut off by the shutdown deadline fail
+    /// with [QueueClosed](AmplitudeError::QueueClosed)
+    async fn flush(&mut self) -> Result<Report, AmplitudeError> {
+        if self.events.is_empty() {
+            return Ok(Report::default());
+        }
+        let events = std::mem::take(&mut self.events);
+        self.bytes = 0;
+        self.amp.metrics().dequeued(events.len());
+        // besides the caller of flush, only the callbacks, dead letter sink and stats of the
+        // client get the outcome, it is not kept so the failures do not pile up until the shutdown
+        let result = {
+            let mut stop = self.stop.clone();
+            let send = self.amp.send(events.clone());
+            tokio::pin!(send);
+            tokio::select! {
+                result = &mut send => Some(result),
+                deadline = Self::deadline(&mut stop) => {
+                    tokio::time::timeout_at(deadline, send).await.ok()
+                }
+            }
+        };
+        result.unwrap_or_else(|| {
+            self.cut_off.extend(events);
+            Err(AmplitudeError::QueueClosed)
+        })
+    }
+
+    /// Waits until a shutdown sets a deadline
+    async fn deadline(stop: &mut watch::Receiver<Option<Instant>>) -> Instant {
+        loop {
+            if let Some(deadline) = *stop.borrow() {
+                return deadline;
+            }
+            if stop.changed().await.is_err() {
+                futures_util::future::pending::<()>().await;
+            }
+        }
+    }
+
+    /// Sends the pending events for the last time
+    async fn finish(&mut self, deadline: Option<Instant>) -> ShutdownSummary {
+        let cut_off = std::mem::take(&mut self.cut_off);
+        let events = std::mem::take(&mut self.events);
+        if events.is_empty() {
+            return ShutdownSummary {
+                delivered: 0,
+                undelivered: cut_off,
+            };
+        }
+        self.amp.metrics().dequeued(events.len());
+        let send = self.amp.send(events.clone());
+        let result = match deadline {
+            Some(deadline) => tokio::time::timeout_at(deadline, send).await.ok(),
+            None => Some(send.await),
+        };
+        let mut summary = match result {
+            Some(Ok(report)) => ShutdownSummary {
+                delivered: report.delivered.len(),
+                undelivered: report.rejected.into_iter().map(|r| r.event).collect(),
+            },
+            Some(Err(AmplitudeError::PartiallySent { report, unsent, .. })) => ShutdownSummary {
+                delivered: report.delivered.len(),
+                undelivered: report
+                    .rejected
+                    .into_iter()
+                    .map(|r| r.event)
+                    .chain(unsent)
+                    .collect(),
+            },
+            _ => ShutdownSummary {
+                delivered: 0,
+                undelivered: events,
+            },
+        };
+        summary.undelivered.splice(0..0, cut_off);
+        summary
+    }
+}
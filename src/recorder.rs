@@ -0,0 +1,125 @@
+use std::fmt::{self, Debug};
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex, PoisonError};
+
+use crate::entities::{Event, UploadBody};
+use crate::report::Report;
+use crate::response::{self, AmplitudeResponse};
+use crate::Amp;
+
+use super::*;
+
+/// Takes the requests of [Amp](crate::Amp) instead of the amplitude servers (dry run)
+///
+/// Every request body is written as one JSON line as it would have been posted
+/// (before compression), but without the api key. Clones share the same output.
+#[derive(Clone, Debug)]
+pub struct Recorder {
+    output: Arc<Mutex<Output>>,
+}
+
+enum Output {
+    Writer(Box<dyn Write + Send>),
+    Memory(Vec<String>),
+}
+
+impl Debug for Output {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Output::Writer(_) => f.write_str("Writer"),
+            Output::Memory(bodies) => f.debug_tuple("Memory").field(&bodies.len()).finish(),
+        }
+    }
+}
+
+impl Recorder {
+    /// Writes the request bodies to the writer
+    pub fn writer<W>(writer: W) -> Self
+    where
+        W: Write + Send + 'static,
+    {
+        Self::with_output(Output::Writer(Box::new(writer)))
+    }
+
+    /// Appends the request bodies to the file, creating it if needed
+    pub fn file<P>(path: P) -> Result<Self, AmplitudeError>
+    where
+        P: AsRef<Path>,
+    {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Self::writer(file))
+    }
+
+    /// Keeps the request bodies in memory, see [recorded](Recorder::recorded)
+    pub fn memory() -> Self {
+        Self::with_output(Output::Memory(Vec::new()))
+    }
+
+    fn with_output(output: Output) -> Self {
+        Self {
+            output: Arc::new(Mutex::new(output)),
+        }
+    }
+
+    /// The request bodies kept in memory so far, empty unless [memory](Recorder::memory) is used
+    pub fn recorded(&self) -> Vec<String> {
+        match &*self.output.lock().unwrap_or_else(PoisonError::into_inner) {
+            Output::Memory(bodies) => bodies.clone(),
+            Output::Writer(_) => Vec::new(),
+        }
+    }
+
+    /// Records the body and answers the way the amplitude servers would on success.
+    /// Returns the response with the size of the body as it would have been posted
+    pub(crate) fn record(
+        &self,
+        body: &UploadBody,
+    ) -> Result<(AmplitudeResponse, usize), AmplitudeError> {
+        let mut value = serde_json::to_value(body)?;
+        // the key would only leak into the files, replay uses the one of the client anyway
+        if let Some(fields) = value.as_object_mut() {
+            fields.remove("api_key");
+        }
+        let line = value.to_string();
+        let mut output = self.output.lock().unwrap_or_else(PoisonError::into_inner);
+        match &mut *output {
+            Output::Writer(writer) => {
+                writer.write_all(line.as_bytes())?;
+                writer.write_all(b"\n")?;
+                writer.flush()?;
+            }
+            Output::Memory(bodies) => bodies.push(line.clone()),
+        }
+        let size = body.size();
+        let response = AmplitudeResponse::Ok(response::Ok::new(body.events.len(), size));
+        Ok((response, size))
+    }
+}
+
+/// A request body as it is recorded
+#[derive(Deserialize)]
+struct Recorded {
+    events: Vec<Event>,
+}
+
+/// Sends the events of the recorded requests with the client.
+///
+/// The recorded options (and the api keys of older recordings) are ignored,
+/// the ones of the client are used instead
+pub async fn replay<P>(amp: &Amp, path: P) -> Result<Report, AmplitudeError>
+where
+    P: AsRef<Path>,
+{
+    let mut events = Vec::new();
+    for line in BufReader::new(File::open(path)?).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let body: Recorded = serde_json::from_str(&line)?;
+        events.extend(body.events);
+    }
+    amp.send(events).await
+}
@@ -0,0 +1,80 @@
+use super::*;
+use crate::entities::Event;
+use crate::response::AmplitudeResponse;
+
+/// The outcome of [Amp::send](crate::Amp::send)
+#[derive(Debug, Clone, Default)]
+#[non_exhaustive]
+pub struct Report {
+    /// Final responses of the amplitude servers
+    pub responses: Vec<AmplitudeResponse>,
+    /// Number of HTTP requests made, including retries
+    pub attempts: u32,
+    /// Events accepted by the amplitude servers
+    pub delivered: Vec<Event>,
+    /// Events which were not accepted, each with the reason
+    pub rejected: Vec<Rejected>,
+}
+
+impl Report {
+    /// Whether every response is a success
+    pub fn is_ok(&self) -> bool {
+        self.responses
+            .iter()
+            .all(|response| matches!(response, AmplitudeResponse::Ok(_)))
+            && self.rejected.is_empty()
+    }
+
+    /// Whether some events were not delivered for a reason which may go away,
+    /// so sending them again later makes sense
+    pub fn is_retryable(&self) -> bool {
+        self.responses.iter().any(|response| {
+            response.is_retryable() || matches!(response, AmplitudeResponse::TooManyRequests(_))
+        })
+    }
+
+    /// Adds the outcome of another request to the report
+    pub(crate) fn merge(&mut self, other: Report) {
+        self.responses.extend(other.responses);
+        self.attempts += other.attempts;
+        self.delivered.extend(other.delivered);
+        self.rejected.extend(other.rejected);
+    }
+
+    /// Records a final unsuccessful response together with the events it rejected
+    pub(crate) fn fail(&mut self, events: Vec<Event>, response: AmplitudeResponse) {
+        self.reject(events, Reason::Response(response.clone()));
+        self.responses.push(response);
+    }
+
+    pub(crate) fn reject(&mut self, events: Vec<Event>, reason: Reason) {
+        self.rejected
+            .extend(events.into_iter().map(|event| Rejected {
+                event,
+                reason: reason.clone(),
+            }));
+    }
+}
+
+/// An event which was not accepted by the amplitude servers
+#[derive(Debug, Clone)]
+pub struct Rejected {
+    pub event: Event,
+    pub reason: Reason,
+}
+
+/// Why an event was not accepted by the amplitude servers
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[non_exhaustive]
+pub enum Reason {
+    /// The event alone exceeds the payload size limit
+    TooLarge,
+    /// The field of the event has an invalid value
+    InvalidField(String),
+    /// The event misses the required field
+    MissingField(String),
+    /// The id field of the event is shorter than the minimum id length
+    InvalidIdLength(String),
+    /// The request containing the event was answered with the response
+    Response(AmplitudeResponse),
+}
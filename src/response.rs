@@ -1,4 +1,8 @@
+use std::time::Duration;
+
 use super::*;
+use crate::report::{Reason, Rejected};
+use crate::Event;
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub enum AmplitudeResponse {
@@ -8,6 +12,49 @@ pub enum AmplitudeResponse {
     TooManyRequests(TooManyRequests),
     ServerError(ServerError),
     ServiceUnavailable(ServiceUnavailable),
+    /// A response the amplitude servers are not documented to give,
+    /// e.g. 401 for an invalid api key or an html page of a proxy
+    Unexpected(Unexpected),
+}
+
+impl AmplitudeResponse {
+    /// Name of the variant
+    pub(crate) fn kind(&self) -> &'static str {
+        match self {
+            AmplitudeResponse::Ok(_) => "Ok",
+            AmplitudeResponse::BadRequest(_) => "BadRequest",
+            AmplitudeResponse::PayloadTooLarge(_) => "PayloadTooLarge",
+            AmplitudeResponse::TooManyRequests(_) => "TooManyRequests",
+            AmplitudeResponse::ServerError(_) => "ServerError",
+            AmplitudeResponse::ServiceUnavailable(_) => "ServiceUnavailable",
+            AmplitudeResponse::Unexpected(_) => "Unexpected",
+        }
+    }
+
+    /// Whether the same request may succeed if it is sent again later
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            AmplitudeResponse::ServerError(_) | AmplitudeResponse::ServiceUnavailable(_) => true,
+            AmplitudeResponse::Unexpected(unexpected) => {
+                matches!(unexpected.status, 408 | 500..=599)
+            }
+            _ => false,
+        }
+    }
+
+    /// Whether the api key is missing or was rejected, which a valid key fixes
+    pub(crate) fn is_unauthorized(&self) -> bool {
+        match self {
+            AmplitudeResponse::BadRequest(bad) => {
+                bad.missing_field() == Some("api_key")
+                    || bad
+                        .error()
+                        .is_some_and(|error| error.starts_with("Invalid API key"))
+            }
+            AmplitudeResponse::Unexpected(unexpected) => matches!(unexpected.status, 401 | 403),
+            _ => false,
+        }
+    }
 }
 
 /// [The official docs](https://developers.amplitude.com/docs/http-api-v2#200-response-successsummary)
@@ -21,6 +68,22 @@ pub struct Ok {
     server_upload_time: Option<u64>,
 }
 
+impl Ok {
+    /// A success summary made up on the client, e.g. by a [Recorder](crate::recorder::Recorder)
+    pub(crate) fn new(events_ingested: usize, payload_size_bytes: usize) -> Self {
+        Self {
+            code: Some(200),
+            events_ingested: Some(events_ingested.min(u16::MAX as usize) as u16),
+            payload_size_bytes: Some(payload_size_bytes as u64),
+            server_upload_time: Some(chrono::Utc::now().timestamp_millis() as u64),
+        }
+    }
+
+    pub fn events_ingested(&self) -> Option<u16> {
+        self.events_ingested
+    }
+}
+
 /// [The official docs](https://developers.amplitude.com/docs/http-api-v2#400-response-invalidrequesterror)
 #[skip_serializing_none]
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -34,6 +97,87 @@ pub struct BadRequest {
     events_with_invalid_id_lengths: Option<SerdeMap>,
 }
 
+impl BadRequest {
+    /// The error message of the response
+    pub fn error(&self) -> Option<&str> {
+        self.error.as_deref()
+    }
+
+    /// A field missing from the request itself (e.g. api_key)
+    pub fn missing_field(&self) -> Option<&str> {
+        self.missing_field.as_deref()
+    }
+
+    /// Map from a field name to indexes of the events with an invalid value of the field
+    pub fn events_with_invalid_fields(&self) -> Option<&SerdeMap> {
+        self.events_with_invalid_fields.as_ref()
+    }
+
+    /// Map from a field name to indexes of the events missing the field
+    pub fn events_with_missing_fields(&self) -> Option<&SerdeMap> {
+        self.events_with_missing_fields.as_ref()
+    }
+
+    /// Map from an id field name to indexes of the events where the id is too short
+    pub fn events_with_invalid_id_lengths(&self) -> Option<&SerdeMap> {
+        self.events_with_invalid_id_lengths.as_ref()
+    }
+
+    /// Splits the events of the request into the rejected ones (each with the reason)
+    /// and the rest. All events are valid if the response does not point to any of them
+    pub(crate) fn split(&self, events: Vec<Event>) -> (Vec<Rejected>, Vec<Event>) {
+        let mut reasons: HashMap<usize, Reason> = HashMap::new();
+        let maps = [
+            (
+                &self.events_with_invalid_fields,
+                Reason::InvalidField as fn(String) -> Reason,
+            ),
+            (&self.events_with_missing_fields, Reason::MissingField),
+            (
+                &self.events_with_invalid_id_lengths,
+                Reason::InvalidIdLength,
+            ),
+        ];
+        for (map, reason) in maps.iter() {
+            for (field, index) in map.iter().flat_map(Self::indexes) {
+                reasons.entry(index).or_insert_with(|| reason(field));
+            }
+        }
+        let mut rejected = Vec::new();
+        let mut rest = Vec::new();
+        for (i, event) in events.into_iter().enumerate() {
+            match reasons.remove(&i) {
+                Some(reason) => rejected.push(Rejected { event, reason }),
+                None => rest.push(event),
+            }
+        }
+        (rejected, rest)
+    }
+
+    /// Pairs of a field name and an event index. The official format is
+    /// `{"field": [indexes]}`, though `{"index": ["fields"]}` is accepted as well
+    fn indexes(map: &SerdeMap) -> Vec<(String, usize)> {
+        let mut pairs = Vec::new();
+        for (key, value) in map {
+            let values = value.as_array().map(Vec::as_slice).unwrap_or_default();
+            match key.parse::<usize>() {
+                Ok(index) => pairs.extend(
+                    values
+                        .iter()
+                        .map(|field| (field.as_str().unwrap_or_default().to_string(), index)),
+                ),
+                Err(_) => pairs.extend(
+                    values
+                        .iter()
+                        .filter_map(|index| index.as_u64())
+                        .map(|index| (key.clone(), index as usize)),
+                ),
+            }
+        }
+        pairs
+    }
+}
+
 #[skip_serializing_none]
 #[derive(Serialize, Deserialize, Debug, Clone)]
 #[non_exhaustive]
@@ -54,6 +198,60 @@ pub struct TooManyRequests {
     throttled_events: Option<Vec<u32>>,
 }
 
+impl TooManyRequests {
+    /// The current events per second threshold of the app
+    pub fn eps_threshold(&self) -> Option<u32> {
+        self.eps_threshold
+    }
+
+    /// Map from device_id to its current number of events per second
+    pub fn throttled_devices(&self) -> Option<&SerdeMap> {
+        self.throttled_devices.as_ref()
+    }
+
+    /// Map from user_id to its current number of events per second
+    pub fn throttled_users(&self) -> Option<&SerdeMap> {
+        self.throttled_users.as_ref()
+    }
+
+    /// Indexes of the throttled events in the request. Empty if unknown
+    pub fn throttled_events(&self) -> &[u32] {
+        self.throttled_events.as_deref().unwrap_or_default()
+    }
+
+    /// How long the throttled users and devices should wait before sending again,
+    /// so their rate falls under the threshold. None if the response has no rates
+    pub fn backoff(&self) -> Option<Duration> {
+        let threshold = f64::from(self.eps_threshold?.max(1));
+        let peak = self
+            .throttled_devices
+            .iter()
+            .chain(self.throttled_users.iter())
+            .flat_map(|map| map.values())
+            .filter_map(|eps| eps.as_f64())
+            .fold(None, |peak: Option<f64>, eps| {
+                Some(peak.map_or(eps, |p| p.max(eps)))
+            })?;
+        Some(Duration::from_secs_f64((peak / threshold).ceil().max(1.0)))
+    }
+
+    /// Splits the events of the request into the throttled ones and the rest
+    pub(crate) fn split(&self, events: Vec<Event>) -> (Vec<Event>, Vec<Event>) {
+        let indexes = self.throttled_events();
+        if indexes.is_empty() {
+            return (events, Vec::new());
+        }
+        let (throttled, rest): (Vec<_>, Vec<_>) = events
+            .into_iter()
+            .enumerate()
+            .partition(|(i, _)| indexes.contains(&(*i as u32)));
+        (
+            throttled.into_iter().map(|(_, event)| event).collect(),
+            rest.into_iter().map(|(_, event)| event).collect(),
+        )
+    }
+}
+
 #[skip_serializing_none]
 #[derive(Serialize, Deserialize, Debug, Clone)]
 #[non_exhaustive]
@@ -67,3 +265,35 @@ pub struct ServerError {
 pub struct ServiceUnavailable {
     value: Option<HashMap<String, serde_json::Value>>, // any value, as it is unknown
 }
+
+/// The response as it was received
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[non_exhaustive]
+pub struct Unexpected {
+    status: u16,
+    headers: Vec<(String, String)>,
+    body: String,
+}
+
+impl Unexpected {
+    pub(crate) fn new(status: u16, headers: Vec<(String, String)>, body: String) -> Self {
+        Self {
+            status,
+            headers,
+            body,
+        }
+    }
+
+    pub fn status(&self) -> u16 {
+        self.status
+    }
+
+    pub fn headers(&self) -> &[(String, String)] {
+        &self.headers
+    }
+
+    /// The body, with invalid UTF-8 sequences replaced
+    pub fn body(&self) -> &str {
+        &self.body
+    }
+}
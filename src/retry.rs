@@ -0,0 +1,80 @@
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::time::Duration;
+
+/// Describes how uploads failed with a retryable outcome are repeated
+///
+/// Retryable outcomes are
+/// [ServerError](crate::response::AmplitudeResponse::ServerError),
+/// [ServiceUnavailable](crate::response::AmplitudeResponse::ServiceUnavailable)
+/// and [NetworkError](crate::AmplitudeError::NetworkError).
+/// The delay before attempt `n + 1` is `base_delay * 2^(n - 1)` capped by `max_delay`.
+#[derive(Clone, Debug)]
+pub struct RetryPolicy {
+    /// Total number of attempts, including the first one
+    pub max_attempts: u32,
+    /// Delay after the first failed attempt
+    pub base_delay: Duration,
+    /// Upper bound of a delay between two attempts
+    pub max_delay: Duration,
+    /// Fraction of the delay (from 0.0 to 1.0) which is randomized
+    pub jitter: f64,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(30),
+            jitter: 0.2,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Delay to wait after the given (1-based) failed attempt
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let exponent = attempt.saturating_sub(1).min(31);
+        let delay = self
+            .base_delay
+            .checked_mul(1 << exponent)
+            .unwrap_or(self.max_delay)
+            .min(self.max_delay);
+        let jitter = self.jitter.clamp(0.0, 1.0);
+        delay.mul_f64(1.0 - jitter * random_fraction())
+    }
+}
+
+/// Describes how events rejected with
+/// [TooManyRequests](crate::response::AmplitudeResponse::TooManyRequests) are resent
+///
+/// Events which were not throttled are resent immediately, the throttled ones are resent
+/// after a [backoff](crate::response::TooManyRequests::backoff) bounded by `min_backoff`
+/// and `max_backoff`.
+#[derive(Clone, Debug)]
+pub struct ThrottlePolicy {
+    /// How many times the same event may be resent
+    pub max_resends: u32,
+    /// Lower bound of the backoff of throttled events
+    pub min_backoff: Duration,
+    /// Upper bound of the backoff of throttled events
+    pub max_backoff: Duration,
+}
+
+impl Default for ThrottlePolicy {
+    fn default() -> Self {
+        Self {
+            max_resends: 3,
+            min_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(30),
+        }
+    }
+}
+
+/// A random number from 0.0 to 1.0, good enough to spread retries apart
+pub(crate) fn random_fraction() -> f64 {
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u64(0);
+    (hasher.finish() >> 11) as f64 / (1u64 << 53) as f64
+}
@@ -0,0 +1,148 @@
+use std::fmt::{self, Debug};
+use std::sync::Arc;
+
+use futures_util::future;
+
+use crate::entities::Event;
+use crate::report::Report;
+use crate::Amp;
+
+use super::*;
+
+type Predicate = Arc<dyn Fn(&Event) -> bool + Send + Sync>;
+type Tag = Arc<dyn Fn(&Event) -> Option<String> + Send + Sync>;
+
+/// Sends events to several amplitude projects, each with its own [Amp](crate::Amp)
+///
+/// A project of an event is chosen by the [tag](AmpRouter::route_by_tag) first,
+/// then by the first matching [route](AmpRouter::route)
+/// and at last the [default project](AmpRouter::default_project) is used.
+/// An event whose project was not [added](AmpRouter::add_project), e.g. because of
+/// a typo in a tag, is left [unrouted](RouterReport::unrouted) rather than sent elsewhere.
+#[derive(Clone, Default)]
+pub struct AmpRouter {
+    projects: HashMap<String, Amp>,
+    tag: Option<Tag>,
+    routes: Vec<(Predicate, String)>,
+    default_project: Option<String>,
+}
+
+/// The outcome of [AmpRouter::send](AmpRouter::send)
+#[derive(Debug, Default)]
+#[non_exhaustive]
+pub struct RouterReport {
+    /// The outcome of sending to every project which got any events
+    pub projects: HashMap<String, Result<Report, AmplitudeError>>,
+    /// Events which did not match any project
+    pub unrouted: Vec<Event>,
+}
+
+impl RouterReport {
+    /// Whether every event was routed and delivered
+    pub fn is_ok(&self) -> bool {
+        self.unrouted.is_empty()
+            && self
+                .projects
+                .values()
+                .all(|result| matches!(result, Ok(report) if report.is_ok()))
+    }
+}
+
+impl Debug for AmpRouter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AmpRouter")
+            .field("projects", &self.projects)
+            .field("tag", &self.tag.is_some())
+            .field(
+                "routes",
+                &self.routes.iter().map(|(_, name)| name).collect::<Vec<_>>(),
+            )
+            .field("default_project", &self.default_project)
+            .finish()
+    }
+}
+
+impl AmpRouter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a project, replacing the one with the same name
+    pub fn add_project<S>(&mut self, name: S, amp: Amp) -> &mut Self
+    where
+        S: Into<String>,
+    {
+        self.projects.insert(name.into(), amp);
+        self
+    }
+
+    pub fn project(&self, name: &str) -> Option<&Amp> {
+        self.projects.get(name)
+    }
+
+    /// Sends the events matching the predicate to the project
+    pub fn route<S, F>(&mut self, name: S, predicate: F) -> &mut Self
+    where
+        S: Into<String>,
+        F: Fn(&Event) -> bool + Send + Sync + 'static,
+    {
+        self.routes.push((Arc::new(predicate), name.into()));
+        self
+    }
+
+    /// Sends every event to the project which name the function returns.
+    /// Events without a tag are routed by the [routes](AmpRouter::route)
+    pub fn route_by_tag<F>(&mut self, tag: F) -> &mut Self
+    where
+        F: Fn(&Event) -> Option<String> + Send + Sync + 'static,
+    {
+        self.tag = Some(Arc::new(tag));
+        self
+    }
+
+    /// Sends the events no route matches to the project
+    pub fn default_project<S>(&mut self, name: S) -> &mut Self
+    where
+        S: Into<String>,
+    {
+        self.default_project = Some(name.into());
+        self
+    }
+
+    /// Name of the project the event goes to, if it is known
+    pub fn project_of(&self, event: &Event) -> Option<&str> {
+        let name = self
+            .tag
+            .as_ref()
+            .and_then(|tag| tag(event))
+            .or_else(|| {
+                self.routes
+                    .iter()
+                    .find(|(predicate, _)| predicate(event))
+                    .map(|(_, name)| name.clone())
+            })
+            .or_else(|| self.default_project.clone())?;
+        self.projects
+            .get_key_value(name.as_str())
+            .map(|(name, _)| name.as_str())
+    }
+
+    /// Splits the events by projects and sends them to all projects at once
+    pub async fn send(&self, events: Vec<Event>) -> RouterReport {
+        let mut batches: HashMap<&str, Vec<Event>> = HashMap::new();
+        let mut unrouted = Vec::new();
+        for event in events {
+            match self.project_of(&event) {
+                Some(name) => batches.entry(name).or_default().push(event),
+                None => unrouted.push(event),
+            }
+        }
+        let sends = batches.into_iter().map(|(name, events)| async move {
+            (name.to_string(), self.projects[name].send(events).await)
+        });
+        RouterReport {
+            projects: future::join_all(sends).await.into_iter().collect(),
+            unrouted,
+        }
+    }
+}
@@ -0,0 +1,54 @@
+use std::str::FromStr;
+
+use super::*;
+
+/// Data center the events are sent to
+///
+/// [The official docs](https://developers.amplitude.com/docs/http-api-v2#endpoints)
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum ServerZone {
+    #[default]
+    US,
+    EU,
+}
+
+impl ServerZone {
+    /// Url of the zone which the endpoint paths are appended to
+    pub fn base_url(&self) -> &'static str {
+        match self {
+            ServerZone::US => "https://api2.amplitude.com",
+            ServerZone::EU => "https://api.eu.amplitude.com",
+        }
+    }
+}
+
+impl FromStr for ServerZone {
+    type Err = AmplitudeError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_uppercase().as_str() {
+            "US" => Ok(ServerZone::US),
+            "EU" => Ok(ServerZone::EU),
+            _ => Err(AmplitudeError::InitializationError(format!(
+                "Unknown server zone {:?}, expected US or EU",
+                s
+            ))),
+        }
+    }
+}
+
+/// An API which accepts events
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Endpoint {
+    Single,
+    Batch,
+}
+
+impl Endpoint {
+    pub fn path(&self) -> &'static str {
+        match self {
+            Endpoint::Single => "/2/httpapi",
+            Endpoint::Batch => "/batch",
+        }
+    }
+}
@@ -0,0 +1,203 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, PoisonError};
+use std::time::Duration;
+
+use crate::response::AmplitudeResponse;
+
+use super::*;
+
+/// What [Amp](crate::Amp) and its clones have done so far
+///
+/// With the `metrics` feature the same values are reported to the
+/// [metrics](https://docs.rs/metrics) facade under the `amplitude_` prefix.
+#[derive(Clone, Debug, Default)]
+#[non_exhaustive]
+pub struct Stats {
+    /// Number of HTTP requests made, including retries
+    pub requests: u64,
+    /// Number of events in the requests made, including retries
+    pub events_sent: u64,
+    /// Number of bytes of the requests made, as they were sent over the wire
+    pub bytes_sent: u64,
+    /// Number of events accepted by the amplitude servers
+    pub events_delivered: u64,
+    /// Number of events which were not delivered for good
+    pub events_failed: u64,
+    /// Number of requests repeated by the retry policy and events resent by the throttle policy
+    pub retries: u64,
+    /// Number of responses by the variant of [AmplitudeResponse](AmplitudeResponse),
+    /// requests which failed with an error are counted as `Error`
+    pub responses: HashMap<&'static str, u64>,
+    /// Time from posting a request until its response, in seconds
+    pub latency: Histogram,
+    /// Number of events in a request
+    pub batch_size: Histogram,
+    /// Number of events waiting in the queues sending with the client
+    pub queue_depth: u64,
+}
+
+/// Distribution of the recorded values
+#[derive(Clone, Debug, Default)]
+#[non_exhaustive]
+pub struct Histogram {
+    pub count: u64,
+    pub sum: f64,
+    /// Upper bounds of the buckets with the number of values in them,
+    /// the values over the last bound are only counted in [count](Histogram::count)
+    pub buckets: Vec<(f64, u64)>,
+}
+
+impl Histogram {
+    fn new(bounds: &[f64]) -> Self {
+        Self {
+            count: 0,
+            sum: 0.0,
+            buckets: bounds.iter().map(|bound| (*bound, 0)).collect(),
+        }
+    }
+
+    fn record(&mut self, value: f64) {
+        self.count += 1;
+        self.sum += value;
+        if let Some((_, count)) = self.buckets.iter_mut().find(|(bound, _)| value <= *bound) {
+            *count += 1;
+        }
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct Metrics {
+    requests: AtomicU64,
+    events_sent: AtomicU64,
+    bytes_sent: AtomicU64,
+    events_delivered: AtomicU64,
+    events_failed: AtomicU64,
+    retries: AtomicU64,
+    queue_depth: AtomicU64,
+    distributions: Mutex<Distributions>,
+}
+
+#[derive(Debug)]
+struct Distributions {
+    responses: HashMap<&'static str, u64>,
+    latency: Histogram,
+    batch_size: Histogram,
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self {
+            requests: AtomicU64::new(0),
+            events_sent: AtomicU64::new(0),
+            bytes_sent: AtomicU64::new(0),
+            events_delivered: AtomicU64::new(0),
+            events_failed: AtomicU64::new(0),
+            retries: AtomicU64::new(0),
+            queue_depth: AtomicU64::new(0),
+            distributions: Mutex::new(Distributions {
+                responses: HashMap::new(),
+                latency: Histogram::new(&[
+                    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0,
+                ]),
+                batch_size: Histogram::new(&[1.0, 10.0, 50.0, 100.0, 250.0, 500.0, 1000.0, 2000.0]),
+            }),
+        }
+    }
+}
+
+impl Metrics {
+    /// Records a request of the events which took the bytes, answered with the outcome
+    pub fn request(
+        &self,
+        events: usize,
+        bytes: usize,
+        latency: Duration,
+        outcome: Result<&AmplitudeResponse, &AmplitudeError>,
+    ) {
+        let kind = match outcome {
+            Ok(response) => response.kind(),
+            Err(_) => "Error",
+        };
+        self.requests.fetch_add(1, Ordering::Relaxed);
+        self.events_sent.fetch_add(events as u64, Ordering::Relaxed);
+        self.bytes_sent.fetch_add(bytes as u64, Ordering::Relaxed);
+        {
+            let mut distributions = self.lock();
+            *distributions.responses.entry(kind).or_default() += 1;
+            distributions.latency.record(latency.as_secs_f64());
+            distributions.batch_size.record(events as f64);
+        }
+        #[cfg(feature = "metrics")]
+        {
+            metrics::counter!("amplitude_requests_total", "response" => kind).increment(1);
+            metrics::counter!("amplitude_events_sent_total").increment(events as u64);
+            metrics::counter!("amplitude_bytes_sent_total").increment(bytes as u64);
+            metrics::histogram!("amplitude_request_duration_seconds").record(latency.as_secs_f64());
+            metrics::histogram!("amplitude_batch_size").record(events as f64);
+        }
+    }
+
+    pub fn delivered(&self, events: usize) {
+        self.events_delivered
+            .fetch_add(events as u64, Ordering::Relaxed);
+        #[cfg(feature = "metrics")]
+        metrics::counter!("amplitude_events_delivered_total").increment(events as u64);
+    }
+
+    pub fn failed(&self, events: usize) {
+        self.events_failed
+            .fetch_add(events as u64, Ordering::Relaxed);
+        #[cfg(feature = "metrics")]
+        metrics::counter!("amplitude_events_failed_total").increment(events as u64);
+    }
+
+    pub fn retry(&self) {
+        self.retries.fetch_add(1, Ordering::Relaxed);
+        #[cfg(feature = "metrics")]
+        metrics::counter!("amplitude_retries_total").increment(1);
+    }
+
+    /// Events were put into a queue
+    pub fn enqueued(&self, events: usize) {
+        self.queue_depth.fetch_add(events as u64, Ordering::Relaxed);
+        self.report_queue_depth();
+    }
+
+    /// Events were taken from a queue, whatever happened to them then
+    pub fn dequeued(&self, events: usize) {
+        let _ = self
+            .queue_depth
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |depth| {
+                Some(depth.saturating_sub(events as u64))
+            });
+        self.report_queue_depth();
+    }
+
+    fn report_queue_depth(&self) {
+        #[cfg(feature = "metrics")]
+        metrics::gauge!("amplitude_queue_depth")
+            .set(self.queue_depth.load(Ordering::Relaxed) as f64);
+    }
+
+    pub fn snapshot(&self) -> Stats {
+        let distributions = self.lock();
+        Stats {
+            requests: self.requests.load(Ordering::Relaxed),
+            events_sent: self.events_sent.load(Ordering::Relaxed),
+            bytes_sent: self.bytes_sent.load(Ordering::Relaxed),
+            events_delivered: self.events_delivered.load(Ordering::Relaxed),
+            events_failed: self.events_failed.load(Ordering::Relaxed),
+            retries: self.retries.load(Ordering::Relaxed),
+            responses: distributions.responses.clone(),
+            latency: distributions.latency.clone(),
+            batch_size: distributions.batch_size.clone(),
+            queue_depth: self.queue_depth.load(Ordering::Relaxed),
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Distributions> {
+        self.distributions
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+}
@@ -0,0 +1,86 @@
+use std::fmt::Debug;
+use std::future::Future;
+use std::pin::Pin;
+
+/// A future returned by [Transport](Transport)
+pub type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+/// Any error of the underlying HTTP client
+pub type TransportError = Box<dyn std::error::Error + Send + Sync>;
+
+/// An HTTP request to the amplitude servers
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct Request {
+    pub url: String,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+/// An HTTP response of the amplitude servers
+#[derive(Debug, Clone)]
+pub struct Response {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+/// HTTP client used by [Amp](crate::Amp) to post requests
+///
+/// Implemented for [reqwest::Client](https://docs.rs/reqwest/0.11/reqwest/struct.Client.html)
+/// when the `reqwest` feature is enabled (default). Implement it to use any other client
+/// or an in-memory fake in tests.
+pub trait Transport: Debug + Send + Sync {
+    /// Posts the request and returns the response, whatever its status is
+    fn post(&self, request: Request) -> BoxFuture<'_, Result<Response, TransportError>>;
+}
+
+impl<T> Transport for std::sync::Arc<T>
+where
+    T: Transport + ?Sized,
+{
+    fn post(&self, request: Request) -> BoxFuture<'_, Result<Response, TransportError>> {
+        (**self).post(request)
+    }
+}
+
+#[cfg(feature = "reqwest")]
+impl Transport for reqwest::Client {
+    fn post(&self, request: Request) -> BoxFuture<'_, Result<Response, TransportError>> {
+        Box::pin(async move {
+            let mut builder = reqwest::Client::post(self, &request.url);
+            for (name, value) in &request.headers {
+                builder = builder.header(name.as_str(), value.as_str());
+            }
+            let response = builder.body(request.body).send().await?;
+            let status = response.status().as_u16();
+            let headers = response
+                .headers()
+                .iter()
+                .map(|(name, value)| {
+                    let value = String::from_utf8_lossy(value.as_bytes()).into_owned();
+                    (name.to_string(), value)
+                })
+                .collect();
+            let body = response.bytes().await?.to_vec();
+            Ok(Response {
+                status,
+                headers,
+                body,
+            })
+        })
+    }
+}
+
+/// Used when no HTTP client is available, fails every request
+#[cfg_attr(feature = "reqwest", allow(dead_code))]
+#[derive(Debug)]
+pub(crate) struct NoTransport;
+
+impl Transport for NoTransport {
+    fn post(&self, _: Request) -> BoxFuture<'_, Result<Response, TransportError>> {
+        Box::pin(async {
+            Err("no transport, enable the `reqwest` feature or call Amp::set_transport".into())
+        })
+    }
+}
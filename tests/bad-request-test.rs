@@ -0,0 +1,36 @@
+#![cfg(feature = "reqwest")]
+
+mod common;
+
+use amplitude::{Amp, Reason};
+use common::{event, Stub, OK};
+
+const INVALID: (u16, &str) = (
+    400,
+    r#"{"code": 400, "error": "Request missing required field",
+        "events_with_invalid_fields": {"time": [1]},
+        "events_with_missing_fields": {"event_type": [2]}}"#,
+);
+
+#[tokio::test]
+async fn drops_invalid_events_and_resends_the_rest() {
+    let stub = Stub::start(vec![INVALID, OK]).await;
+    let mut amp = Amp::new("key");
+    amp.set_base_url(&stub.url).drop_invalid_events(true);
+    let events = vec![
+        event("a", "bad request"),
+        event("b", "bad request"),
+        event("c", "bad request"),
+        event("d", "bad request"),
+    ];
+    let report = amp.send(events.clone()).await.unwrap();
+
+    assert_eq!(report.attempts, 2);
+    assert_eq!(report.delivered, vec![events[0].clone(), events[3].clone()]);
+    assert_eq!(report.rejected.len(), 2);
+    assert_eq!(report.rejected[0].event, events[1]);
+    assert!(matches!(&report.rejected[0].reason, Reason::InvalidField(f) if f == "time"));
+    assert_eq!(report.rejected[1].event, events[2]);
+    assert!(matches!(&report.rejected[1].reason, Reason::MissingField(f) if f == "event_type"));
+    assert_eq!(stub.requests()[1]["events"].as_array().unwrap().len(), 2);
+}
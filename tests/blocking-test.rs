@@ -0,0 +1,25 @@
+#![cfg(feature = "blocking")]
+
+mod common;
+
+use amplitude::{blocking, Amp, Event};
+use common::Fake;
+
+#[test]
+fn sends_without_async_runtime() {
+    let fake = Fake::new(200);
+    let mut amp = Amp::new("key");
+    amp.set_transport(fake.clone());
+    let mut amp = blocking::Amp::from(amp);
+    amp.batch().set_min_id_length(4);
+
+    let mut event = Event::new();
+    event.user_id("blocking-user").event_type("blocking");
+    let report = amp.send_one(event).unwrap();
+
+    assert!(report.is_ok());
+    let requests = fake.posted();
+    assert_eq!(requests[0].url, "https://api2.amplitude.com/batch");
+    let body: serde_json::Value = serde_json::from_slice(&requests[0].body).unwrap();
+    assert_eq!(body["options"]["min_id_length"], 4);
+}
@@ -0,0 +1,59 @@
+mod common;
+
+use std::time::Duration;
+
+use amplitude::dead_letter::{self, Failure};
+use amplitude::{Amp, AmplitudeError, BreakerPolicy, BreakerState, NdjsonSink};
+use common::{event, temp_dir, Fake};
+
+#[tokio::test]
+async fn opens_after_failures_and_probes_recovery() {
+    let path = temp_dir("breaker").join("dead.ndjson");
+    let fake = Fake::new(503);
+    let mut amp = Amp::new("key");
+    amp.set_transport(fake.clone())
+        .set_dead_letter_sink(NdjsonSink::new(&path).unwrap())
+        .set_circuit_breaker(BreakerPolicy {
+            failure_threshold: 2,
+            open_for: Duration::from_millis(100),
+        });
+
+    amp.send_one(event("breaker-user", "breaker"))
+        .await
+        .unwrap();
+    assert_eq!(amp.circuit_state(), Some(BreakerState::Closed));
+    amp.send_one(event("breaker-user", "breaker"))
+        .await
+        .unwrap();
+    assert_eq!(amp.circuit_state(), Some(BreakerState::Open));
+    let err = amp
+        .send_one(event("breaker-user", "breaker"))
+        .await
+        .unwrap_err();
+    assert!(matches!(err, AmplitudeError::CircuitOpen));
+    assert_eq!(fake.requests().len(), 2);
+    // only the short-circuited event is dead, the others failed with a retryable response
+    let letters = dead_letter::read(&path).unwrap();
+    assert_eq!(letters.len(), 1);
+    assert_eq!(letters[0].event, event("breaker-user", "breaker"));
+    assert!(matches!(letters[0].failure, Failure::Error(_)));
+
+    // a failed probe opens the circuit again
+    tokio::time::sleep(Duration::from_millis(120)).await;
+    assert_eq!(amp.circuit_state(), Some(BreakerState::HalfOpen));
+    amp.send_one(event("breaker-user", "breaker"))
+        .await
+        .unwrap();
+    assert_eq!(amp.circuit_state(), Some(BreakerState::Open));
+    assert_eq!(fake.requests().len(), 3);
+
+    tokio::time::sleep(Duration::from_millis(120)).await;
+    fake.set_status(200);
+    let report = amp
+        .send_one(event("breaker-user", "breaker"))
+        .await
+        .unwrap();
+    assert!(report.is_ok());
+    assert_eq!(amp.circuit_state(), Some(BreakerState::Closed));
+    assert_eq!(fake.requests().len(), 4);
+}
@@ -0,0 +1,105 @@
+mod common;
+
+use std::time::Duration;
+
+use amplitude::{AmpBuilder, AmplitudeError, Event, ServerZone};
+use common::{Fake, Hanging};
+
+#[tokio::test]
+async fn builds_configured_client() {
+    let fake = Fake::new(200);
+    let mut defaults = Event::new();
+    defaults.platform("server").app_version("1.2.3");
+    let amp = AmpBuilder::new("key")
+        .batch()
+        .server_zone(ServerZone::EU)
+        .min_id_length(3)
+        .default_event(defaults)
+        .transport(fake.clone())
+        .build()
+        .unwrap();
+    assert_eq!(amp.url(), "https://api.eu.amplitude.com/batch");
+
+    let mut event = Event::new();
+    event.user_id("abc").event_type("built").platform("ios");
+    amp.send_one(event).await.unwrap();
+    let request = &fake.requests()[0];
+    assert_eq!(request["options"]["min_id_length"], 3);
+    assert_eq!(request["events"][0]["platform"], "ios");
+    assert_eq!(request["events"][0]["app_version"], "1.2.3");
+}
+
+#[tokio::test]
+async fn does_not_copy_identity_of_default_event() {
+    let fake = Fake::new(200);
+    let mut defaults = Event::new();
+    defaults
+        .platform("server")
+        .insert_id("same")
+        .event_id(7)
+        .time(chrono::Utc::now());
+    let amp = AmpBuilder::new("key")
+        .default_event(defaults)
+        .transport(fake.clone())
+        .build()
+        .unwrap();
+
+    let mut event = Event::new();
+    event.user_id("user").event_type("identity");
+    amp.send(vec![event.clone(), event]).await.unwrap();
+    let events = &fake.requests()[0]["events"];
+    for event in events.as_array().unwrap() {
+        assert_eq!(event["platform"], "server");
+        assert!(event.get("insert_id").is_none());
+        assert!(event.get("event_id").is_none());
+        assert!(event.get("time").is_none());
+    }
+}
+
+#[tokio::test]
+async fn times_out_requests() {
+    let amp = AmpBuilder::new("key")
+        .timeout(Duration::from_millis(50))
+        .transport(Hanging)
+        .build()
+        .unwrap();
+    let mut event = Event::new();
+    event.user_id("user").event_type("slow");
+    let err = amp.send_one(event).await.unwrap_err();
+    assert!(matches!(err, AmplitudeError::NetworkError(_)));
+}
+
+#[test]
+fn reports_all_invalid_values() {
+    let err = AmpBuilder::new(" ")
+        .base_url("ftp://example.com")
+        .build()
+        .unwrap_err();
+    match err {
+        AmplitudeError::InvalidConfig(errors) => assert_eq!(errors.len(), 2),
+        err => panic!("unexpected error {:?}", err),
+    }
+}
+
+// the only test touching the environment, so it is not raced by the others
+#[test]
+fn reads_environment() {
+    std::env::remove_var("AMPLITUDE_API_KEY");
+    std::env::set_var("AMPLITUDE_ENDPOINT", "bulk");
+    std::env::set_var("AMPLITUDE_SERVER_ZONE", "EU");
+    std::env::set_var("AMPLITUDE_TIMEOUT_MS", "soon");
+    std::env::set_var("AMPLITUDE_MIN_ID_LENGTH", "3");
+    match AmpBuilder::from_env().unwrap_err() {
+        AmplitudeError::InvalidConfig(errors) => {
+            assert_eq!(errors.len(), 3, "{:?}", errors);
+            assert!(errors[0].contains("AMPLITUDE_API_KEY"));
+        }
+        err => panic!("unexpected error {:?}", err),
+    }
+
+    std::env::set_var("AMPLITUDE_API_KEY", "key");
+    std::env::set_var("AMPLITUDE_ENDPOINT", "batch");
+    std::env::set_var("AMPLITUDE_TIMEOUT_MS", "1000");
+    let amp = AmpBuilder::from_env().unwrap().build().unwrap();
+    assert_eq!(amp.url(), "https://api.eu.amplitude.com/batch");
+}
@@ -0,0 +1,57 @@
+mod common;
+
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use amplitude::response::AmplitudeResponse;
+use amplitude::{Amp, Cause, RetryPolicy};
+use common::{event, Fake};
+
+#[tokio::test]
+async fn notifies_of_outcomes() {
+    let log = Arc::new(Mutex::new(Vec::new()));
+    let fake = Fake::new(500);
+    let mut amp = Amp::new("key");
+    let (delivered, failed, retried) = (log.clone(), log.clone(), log.clone());
+    amp.set_transport(fake.clone())
+        .set_retry_policy(RetryPolicy {
+            max_attempts: 2,
+            base_delay: Duration::from_millis(1),
+            max_delay: Duration::from_millis(1),
+            jitter: 0.0,
+        })
+        .on_delivered(move |events, response| {
+            assert!(matches!(response, AmplitudeResponse::Ok(_)));
+            delivered
+                .lock()
+                .unwrap()
+                .push(format!("delivered {}", events.len()));
+        })
+        .on_failed(move |events, cause| {
+            assert!(matches!(
+                cause,
+                Cause::Response(AmplitudeResponse::ServerError(_))
+            ));
+            failed
+                .lock()
+                .unwrap()
+                .push(format!("failed {}", events.len()));
+        })
+        .on_retry(move |events, attempt, _| {
+            retried
+                .lock()
+                .unwrap()
+                .push(format!("retry {} of {}", attempt, events.len()));
+        });
+
+    amp.send(vec![event("a", "callback"), event("b", "callback")])
+        .await
+        .unwrap();
+    fake.set_status(200);
+    amp.send_one(event("c", "callback")).await.unwrap();
+
+    assert_eq!(
+        *log.lock().unwrap(),
+        vec!["retry 1 of 2", "failed 2", "delivered 1"]
+    );
+}
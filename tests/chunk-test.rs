@@ -0,0 +1,46 @@
+#![cfg(feature = "reqwest")]
+
+mod common;
+
+use amplitude::{Amp, Event, Limits};
+use common::{event, Stub, OK};
+
+fn events(count: usize) -> Vec<Event> {
+    (0..count)
+        .map(|i| event(&format!("user-{}", i), "chunk"))
+        .collect()
+}
+
+#[tokio::test]
+async fn splits_by_event_count() {
+    let stub = Stub::start(vec![OK]).await;
+    let mut amp = Amp::new("key");
+    amp.set_base_url(&stub.url)
+        .set_concurrency(2)
+        .set_limits(Limits {
+            max_events: 2,
+            max_bytes: 1024 * 1024,
+        });
+    let events = events(5);
+    let report = amp.send(events.clone()).await.unwrap();
+    assert_eq!(report.attempts, 3);
+    assert_eq!(report.responses.len(), 3);
+    assert_eq!(report.delivered, events);
+}
+
+#[tokio::test]
+async fn splits_by_payload_size() {
+    let stub = Stub::start(vec![OK]).await;
+    let mut amp = Amp::new("key");
+    amp.set_base_url(&stub.url).set_limits(Limits {
+        max_events: 2000,
+        max_bytes: 150,
+    });
+    let report = amp.send(events(4)).await.unwrap();
+    assert!(report.is_ok());
+    let requests = stub.requests();
+    assert!(requests.len() > 1);
+    for request in requests {
+        assert!(serde_json::to_vec(&request).unwrap().len() <= 150);
+    }
+}
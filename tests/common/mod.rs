@@ -0,0 +1,233 @@
+#![allow(dead_code)]
+
+use std::io::Read;
+use std::sync::atomic::{AtomicU16, Ordering};
+use std::sync::{Arc, Mutex};
+
+use amplitude::transport::{BoxFuture, Request, Response, TransportError};
+use amplitude::{Event, Transport};
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+pub const OK: (u16, &str) = (
+    200,
+    r#"{"code": 200, "events_ingested": 1, "payload_size_bytes": 50, "server_upload_time": 1}"#,
+);
+pub const SERVER_ERROR: (u16, &str) = (500, r#"{"error": "internal"}"#);
+pub const UNAVAILABLE: (u16, &str) = (503, r#"{"error": "unavailable"}"#);
+
+/// A local HTTP server answering with the scripted responses in order.
+/// The last response is repeated once the script runs out
+pub struct Stub {
+    pub url: String,
+    requests: Arc<Mutex<Vec<serde_json::Value>>>,
+    sizes: Arc<Mutex<Vec<usize>>>,
+}
+
+impl Stub {
+    pub async fn start(responses: Vec<(u16, &'static str)>) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let sizes = Arc::new(Mutex::new(Vec::new()));
+        let recorded = requests.clone();
+        let recorded_sizes = sizes.clone();
+        tokio::spawn(async move {
+            let mut index = 0;
+            loop {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let (size, body) = read_body(&mut stream).await;
+                recorded_sizes.lock().unwrap().push(size);
+                recorded
+                    .lock()
+                    .unwrap()
+                    .push(serde_json::from_slice(&body).unwrap_or_default());
+                let (status, text) = responses[index.min(responses.len() - 1)];
+                index += 1;
+                let reply = format!(
+                    "HTTP/1.1 {} Stub\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    status,
+                    text.len(),
+                    text
+                );
+                let _ = stream.write_all(reply.as_bytes()).await;
+            }
+        });
+        Self {
+            url,
+            requests,
+            sizes,
+        }
+    }
+
+    /// Bodies of the requests received so far
+    pub fn requests(&self) -> Vec<serde_json::Value> {
+        self.requests.lock().unwrap().clone()
+    }
+
+    /// Sizes of the request bodies as they were received, i.e. before decompression
+    pub fn sizes(&self) -> Vec<usize> {
+        self.sizes.lock().unwrap().clone()
+    }
+}
+
+async fn read_body(stream: &mut TcpStream) -> (usize, Vec<u8>) {
+    let mut data = Vec::new();
+    let mut buf = [0u8; 4096];
+    let header_end = loop {
+        let n = stream.read(&mut buf).await.unwrap();
+        data.extend_from_slice(&buf[..n]);
+        if let Some(pos) = data.windows(4).position(|w| w == b"\r\n\r\n") {
+            break pos + 4;
+        }
+        if n == 0 {
+            return (0, Vec::new());
+        }
+    };
+    let headers = String::from_utf8_lossy(&data[..header_end]).to_lowercase();
+    let length = headers
+        .lines()
+        .find_map(|line| line.strip_prefix("content-length:"))
+        .and_then(|value| value.trim().parse::<usize>().ok())
+        .unwrap_or(0);
+    while data.len() < header_end + length {
+        let n = stream.read(&mut buf).await.unwrap();
+        if n == 0 {
+            break;
+        }
+        data.extend_from_slice(&buf[..n]);
+    }
+    let body = data[header_end..].to_vec();
+    if headers.contains("content-encoding: gzip") {
+        let mut decoded = Vec::new();
+        flate2::read::GzDecoder::new(body.as_slice())
+            .read_to_end(&mut decoded)
+            .unwrap();
+        return (body.len(), decoded);
+    }
+    (body.len(), body)
+}
+
+/// An in-memory transport answering with the current status,
+/// with the ok body for 200 and an empty object otherwise, unless the body is given
+#[derive(Debug, Clone)]
+pub struct Fake {
+    status: Arc<AtomicU16>,
+    body: Option<&'static str>,
+    answers: Option<usize>,
+    posted: Arc<Mutex<Vec<Request>>>,
+    requests: Arc<Mutex<Vec<serde_json::Value>>>,
+}
+
+impl Fake {
+    pub fn new(status: u16) -> Self {
+        Self {
+            status: Arc::new(AtomicU16::new(status)),
+            body: None,
+            answers: None,
+            posted: Arc::default(),
+            requests: Arc::default(),
+        }
+    }
+
+    /// Answers with the status and always the same body
+    pub fn with_body(status: u16, body: &'static str) -> Self {
+        Self {
+            body: Some(body),
+            ..Self::new(status)
+        }
+    }
+
+    /// Answers the first requests with the status, the later ones fail with an error
+    pub fn failing_after(status: u16, answers: usize) -> Self {
+        Self {
+            answers: Some(answers),
+            ..Self::new(status)
+        }
+    }
+
+    pub fn set_status(&self, status: u16) {
+        self.status.store(status, Ordering::SeqCst);
+    }
+
+    /// Bodies of the requests received so far
+    pub fn requests(&self) -> Vec<serde_json::Value> {
+        self.requests.lock().unwrap().clone()
+    }
+
+    /// The requests received so far, as they were posted
+    pub fn posted(&self) -> Vec<Request> {
+        self.posted.lock().unwrap().clone()
+    }
+
+    /// Values of the field of all events received so far
+    pub fn sent(&self, field: &str) -> Vec<String> {
+        self.requests()
+            .iter()
+            .flat_map(|request| request["events"].as_array().cloned().unwrap_or_default())
+            .map(|event| event[field].as_str().unwrap_or_default().to_string())
+            .collect()
+    }
+}
+
+impl Transport for Fake {
+    fn post(&self, request: Request) -> BoxFuture<'_, Result<Response, TransportError>> {
+        let status = self.status.load(Ordering::SeqCst);
+        self.requests
+            .lock()
+            .unwrap()
+            .push(serde_json::from_slice(&request.body).unwrap_or_default());
+        let mut posted = self.posted.lock().unwrap();
+        posted.push(request);
+        if self.answers.is_some_and(|answers| posted.len() > answers) {
+            return Box::pin(async { Err("offline".into()) });
+        }
+        drop(posted);
+        let body = match self.body {
+            Some(body) => body,
+            None if status == 200 => OK.1,
+            None => "{}",
+        };
+        Box::pin(async move {
+            Ok(Response {
+                status,
+                headers: Vec::new(),
+                body: body.as_bytes().to_vec(),
+            })
+        })
+    }
+}
+
+/// A transport which never answers
+#[derive(Debug)]
+pub struct Hanging;
+
+impl Transport for Hanging {
+    fn post(&self, _: Request) -> BoxFuture<'_, Result<Response, TransportError>> {
+        Box::pin(futures_util::future::pending())
+    }
+}
+
+/// An event of the user with the type
+pub fn event(user_id: &str, event_type: &str) -> Event {
+    let mut event = Event::new();
+    event.user_id(user_id).event_type(event_type);
+    event
+}
+
+/// A fresh directory in the system temp dir
+pub fn temp_dir(name: &str) -> std::path::PathBuf {
+    let nanos = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_nanos();
+    let dir = std::env::temp_dir().join(format!(
+        "amplitude-{}-{}-{}",
+        name,
+        std::process::id(),
+        nanos
+    ));
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
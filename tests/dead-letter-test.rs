@@ -0,0 +1,76 @@
+mod common;
+
+use std::time::Duration;
+
+use amplitude::dead_letter::{self, Failure};
+use amplitude::{Amp, NdjsonSink, Reason, RetryPolicy};
+use common::{event, temp_dir, Fake};
+
+#[tokio::test]
+async fn records_rejected_events_and_redrives_them() {
+    let path = temp_dir("dead-letters").join("dead.ndjson");
+    let fake = Fake::new(400);
+    let mut amp = Amp::new("key");
+    amp.set_transport(fake.clone())
+        .set_dead_letter_sink(NdjsonSink::new(&path).unwrap());
+    let report = amp
+        .send(vec![event("a", "dead letter"), event("b", "dead letter")])
+        .await
+        .unwrap();
+    assert_eq!(report.rejected.len(), 2);
+
+    let letters = dead_letter::read(&path).unwrap();
+    assert_eq!(letters.len(), 2);
+    assert_eq!(letters[0].event, event("a", "dead letter"));
+    assert!(matches!(
+        letters[0].failure,
+        Failure::Rejected(Reason::Response(_))
+    ));
+
+    fake.set_status(200);
+    let mut amp = Amp::new("key");
+    amp.set_transport(fake.clone());
+    let report = dead_letter::redrive(&amp, &path).await.unwrap();
+    assert_eq!(
+        report.delivered,
+        vec![event("a", "dead letter"), event("b", "dead letter")]
+    );
+}
+
+#[tokio::test]
+async fn records_events_of_failed_requests() {
+    let path = temp_dir("dead-letters").join("dead.ndjson");
+    let mut amp = Amp::new("key");
+    amp.set_base_url("http://127.0.0.1:1")
+        .set_retry_policy(RetryPolicy {
+            max_attempts: 1,
+            ..RetryPolicy::default()
+        })
+        .set_dead_letter_sink(NdjsonSink::new(&path).unwrap());
+    assert!(amp.send_one(event("a", "dead letter")).await.is_err());
+
+    let letters = dead_letter::read(&path).unwrap();
+    assert_eq!(letters.len(), 1);
+    assert!(matches!(letters[0].failure, Failure::Error(_)));
+}
+
+#[tokio::test]
+async fn leaves_retryable_failures_to_the_caller() {
+    let path = temp_dir("dead-letters").join("dead.ndjson");
+    let mut amp = Amp::new("key");
+    amp.set_transport(Fake::new(503))
+        .set_dead_letter_sink(NdjsonSink::new(&path).unwrap());
+    let report = amp.send_one(event("a", "dead letter")).await.unwrap();
+    assert!(report.is_retryable());
+    assert!(dead_letter::read(&path).unwrap().is_empty());
+
+    amp.set_retry_policy(RetryPolicy {
+        max_attempts: 2,
+        base_delay: Duration::from_millis(1),
+        ..RetryPolicy::default()
+    });
+    amp.send_one(event("b", "dead letter")).await.unwrap();
+    let letters = dead_letter::read(&path).unwrap();
+    assert_eq!(letters.len(), 1);
+    assert_eq!(letters[0].event, event("b", "dead letter"));
+}
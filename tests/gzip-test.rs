@@ -0,0 +1,59 @@
+#![cfg(feature = "gzip")]
+
+mod common;
+
+use amplitude::{Amp, Event, Limits};
+use common::{event, Stub, OK};
+
+fn events(count: usize) -> Vec<Event> {
+    (0..count)
+        .map(|i| {
+            let mut event = event(&format!("user-{}", i), "a rather repetitive event type");
+            event.country("United Kingdom");
+            event
+        })
+        .collect()
+}
+
+#[tokio::test]
+async fn sends_compressed_body() {
+    let stub = Stub::start(vec![OK]).await;
+    let mut amp = Amp::new("key");
+    amp.set_base_url(&stub.url).gzip(6);
+    let events = events(50);
+    let report = amp.send(events.clone()).await.unwrap();
+    assert!(report.is_ok());
+
+    let request = &stub.requests()[0];
+    assert_eq!(request["events"].as_array().unwrap().len(), 50);
+    assert!(stub.sizes()[0] < serde_json::to_vec(request).unwrap().len() / 2);
+}
+
+#[tokio::test]
+async fn limits_apply_to_compressed_size() {
+    let stub = Stub::start(vec![OK]).await;
+    let mut amp = Amp::new("key");
+    amp.set_base_url(&stub.url).gzip(9).set_limits(Limits {
+        max_events: 2000,
+        max_bytes: 400,
+    });
+    let report = amp.send(events(100)).await.unwrap();
+    assert_eq!(report.delivered.len(), 100);
+    assert!(stub.sizes().iter().all(|size| *size <= 400));
+    // uncompressed the same events take several times more requests
+    let uncompressed = serde_json::to_vec(&stub.requests()).unwrap().len();
+    assert!(uncompressed / 400 > stub.sizes().len());
+}
+
+#[tokio::test]
+async fn zero_max_events_sends_one_event_per_request() {
+    let stub = Stub::start(vec![OK]).await;
+    let mut amp = Amp::new("key");
+    amp.set_base_url(&stub.url).gzip(6).set_limits(Limits {
+        max_events: 0,
+        max_bytes: 1024 * 1024,
+    });
+    let report = amp.send(events(3)).await.unwrap();
+    assert_eq!(report.delivered.len(), 3);
+    assert_eq!(stub.sizes().len(), 3);
+}
@@ -0,0 +1,64 @@
+mod common;
+
+use std::time::Duration;
+
+use amplitude::{Amp, Event, InsertIds, RetryPolicy};
+use common::{event, Fake};
+
+fn timed(event_type: &str) -> Event {
+    let mut event = event("dedup-user", event_type);
+    event.time(chrono::Utc::now());
+    event
+}
+
+#[tokio::test]
+async fn assigns_random_ids_once() {
+    let fake = Fake::new(503);
+    let mut amp = Amp::new("key");
+    amp.set_transport(fake.clone())
+        .set_insert_ids(InsertIds::Random)
+        .set_retry_policy(RetryPolicy {
+            max_attempts: 2,
+            base_delay: Duration::from_millis(1),
+            max_delay: Duration::from_millis(1),
+            jitter: 0.0,
+        });
+    let mut own = timed("own");
+    own.insert_id("own-id");
+    amp.send(vec![timed("a"), timed("a"), own]).await.unwrap();
+
+    let ids = fake.sent("insert_id");
+    assert_eq!(ids.len(), 6);
+    assert_eq!(ids[..3], ids[3..]);
+    assert_ne!(ids[0], ids[1]);
+    assert_eq!(ids[2], "own-id");
+}
+
+#[tokio::test]
+async fn hashes_same_events_to_same_ids() {
+    let fake = Fake::new(200);
+    let mut amp = Amp::new("key");
+    amp.set_transport(fake.clone())
+        .set_insert_ids(InsertIds::Hashed);
+    let first = timed("a");
+    amp.send(vec![first.clone(), timed("b")]).await.unwrap();
+    amp.send_one(first).await.unwrap();
+
+    let ids = fake.sent("insert_id");
+    assert_eq!(ids[0], ids[2]);
+    assert_ne!(ids[0], ids[1]);
+}
+
+#[tokio::test]
+async fn events_without_time_get_random_ids() {
+    let fake = Fake::new(200);
+    let mut amp = Amp::new("key");
+    amp.set_transport(fake.clone())
+        .set_insert_ids(InsertIds::Hashed);
+    let untimed = event("dedup-user", "a");
+    amp.send(vec![untimed.clone(), untimed]).await.unwrap();
+
+    let ids = fake.sent("insert_id");
+    assert_eq!(ids.len(), 2);
+    assert_ne!(ids[0], ids[1]);
+}
@@ -0,0 +1,79 @@
+mod common;
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use amplitude::{Amp, ApiKey};
+use common::{event, temp_dir, Fake};
+
+fn sent_keys(fake: &Fake) -> Vec<String> {
+    fake.requests()
+        .iter()
+        .map(|request| request["api_key"].as_str().unwrap().to_string())
+        .collect()
+}
+
+#[tokio::test]
+async fn redacts_and_rotates_key() {
+    let fake = Fake::new(200);
+    let mut amp = Amp::new("secret-key");
+    amp.set_transport(fake.clone());
+    assert!(!format!("{:?}", amp).contains("secret-key"));
+    assert_eq!(ApiKey::new("secret-key").to_string(), "***");
+
+    let clone = amp.clone();
+    clone.send_one(event("key-user", "key")).await.unwrap();
+    amp.rotate_api_key("rotated-key");
+    clone.send_one(event("key-user", "key")).await.unwrap();
+    assert_eq!(sent_keys(&fake), vec!["secret-key", "rotated-key"]);
+}
+
+#[tokio::test]
+async fn reads_key_from_file() {
+    let path = temp_dir("key").join("api-key");
+    std::fs::write(&path, "first-key\n").unwrap();
+    let fake = Fake::new(200);
+    let mut amp = Amp::new("");
+    let file = path.clone();
+    amp.set_transport(fake.clone())
+        .set_api_key_provider(Duration::from_millis(50), move || ApiKey::from_file(&file))
+        .unwrap();
+    amp.send_one(event("key-user", "key")).await.unwrap();
+
+    std::fs::write(&path, "second-key\n").unwrap();
+    tokio::time::sleep(Duration::from_millis(150)).await;
+    amp.send_one(event("key-user", "key")).await.unwrap();
+
+    // a key which cannot be read keeps the previous one
+    std::fs::remove_file(&path).unwrap();
+    tokio::time::sleep(Duration::from_millis(150)).await;
+    amp.send_one(event("key-user", "key")).await.unwrap();
+    assert_eq!(
+        sent_keys(&fake),
+        vec!["first-key", "second-key", "second-key"]
+    );
+}
+
+#[tokio::test]
+async fn refreshes_key_off_the_send_path() {
+    let calls = Arc::new(AtomicUsize::new(0));
+    let counter = calls.clone();
+    let fake = Fake::new(200);
+    let mut amp = Amp::new("");
+    amp.set_transport(fake.clone())
+        .set_api_key_provider(Duration::from_millis(10), move || {
+            if counter.fetch_add(1, Ordering::SeqCst) > 0 {
+                std::thread::sleep(Duration::from_secs(1));
+            }
+            Ok(ApiKey::new("slow-key"))
+        })
+        .unwrap();
+    amp.send_one(event("key-user", "key")).await.unwrap();
+    tokio::time::sleep(Duration::from_millis(30)).await;
+
+    let started = Instant::now();
+    amp.send_one(event("key-user", "key")).await.unwrap();
+    assert!(started.elapsed() < Duration::from_millis(500));
+    assert!(calls.load(Ordering::SeqCst) > 1);
+}
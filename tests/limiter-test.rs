@@ -0,0 +1,80 @@
+mod common;
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use amplitude::{Amp, AmplitudeError, Event, Rate, RateLimits};
+use common::{event, Fake};
+
+fn on_device(device_id: &str) -> Event {
+    let mut event = event("limited-user", "limited");
+    event.device_id(device_id);
+    event
+}
+
+#[tokio::test]
+async fn delays_events_over_the_device_rate() {
+    let fake = Fake::new(200);
+    let mut amp = Amp::new("key");
+    amp.set_transport(fake.clone()).set_rate_limits(RateLimits {
+        per_device: Some(Rate {
+            events_per_second: 5.0,
+            burst: 2,
+        }),
+        per_user: None,
+        global: None,
+    });
+    let started = Instant::now();
+    let report = amp
+        .send(vec![
+            on_device("hot"),
+            on_device("hot"),
+            on_device("cold"),
+            on_device("hot"),
+        ])
+        .await
+        .unwrap();
+
+    assert!(started.elapsed() >= Duration::from_millis(200));
+    assert_eq!(report.delivered.len(), 4);
+    let requests = fake.requests();
+    assert_eq!(requests.len(), 2);
+    assert_eq!(requests[0]["events"].as_array().unwrap().len(), 3);
+    assert_eq!(requests[1]["events"][0]["device_id"], "hot");
+
+    let stats = amp.rate_limiter_stats().unwrap();
+    assert_eq!(stats.delayed_events, 1);
+    assert_eq!(stats.devices, 2);
+}
+
+#[tokio::test]
+async fn sends_later_waves_after_a_failed_one() {
+    let failed = Arc::new(AtomicUsize::new(0));
+    let counter = failed.clone();
+    let mut amp = Amp::new("key");
+    amp.set_transport(Fake::failing_after(200, 1))
+        .set_rate_limits(RateLimits {
+            per_device: Some(Rate {
+                events_per_second: 20.0,
+                burst: 1,
+            }),
+            per_user: None,
+            global: None,
+        })
+        .on_failed(move |events, _| {
+            counter.fetch_add(events.len(), Ordering::SeqCst);
+        });
+    let result = amp
+        .send(vec![on_device("hot"), on_device("hot"), on_device("hot")])
+        .await;
+
+    match result {
+        Err(AmplitudeError::PartiallySent { report, unsent, .. }) => {
+            assert_eq!(report.delivered, vec![on_device("hot")]);
+            assert_eq!(unsent.len(), 2);
+        }
+        result => panic!("unexpected result {:?}", result),
+    }
+    assert_eq!(failed.load(Ordering::SeqCst), 2);
+}
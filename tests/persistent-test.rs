@@ -0,0 +1,156 @@
+mod common;
+
+use std::time::Duration;
+
+use amplitude::persistent::DiskQueue;
+use amplitude::{Amp, AmplitudeError, DiskQueueConfig, PersistentQueue};
+use common::{event, temp_dir, Fake};
+
+fn config(name: &str) -> DiskQueueConfig {
+    let mut config = DiskQueueConfig::new(temp_dir(name));
+    config.interval = Duration::from_secs(3600);
+    config
+}
+
+fn amp(fake: &Fake) -> Amp {
+    let mut amp = Amp::new("key");
+    amp.set_transport(fake.clone());
+    amp
+}
+
+#[tokio::test]
+async fn replays_unsent_events_after_restart() {
+    let config = config("replay");
+    let fake = Fake::new(503);
+    let queue = PersistentQueue::open(amp(&fake), config.clone()).unwrap();
+    for user_id in &["a", "b", "c"] {
+        queue.track(event(user_id, "persistent")).unwrap();
+    }
+    assert!(!queue.flush().await.unwrap());
+    queue.shutdown().await.unwrap();
+
+    fake.set_status(200);
+    let queue = PersistentQueue::open(amp(&fake), config.clone()).unwrap();
+    assert!(queue.flush().await.unwrap());
+    queue.shutdown().await.unwrap();
+    assert_eq!(
+        fake.sent("user_id")[fake.sent("user_id").len() - 3..],
+        ["a", "b", "c"]
+    );
+
+    // acknowledged events are not sent again
+    let sent = fake.requests().len();
+    let queue = PersistentQueue::open(amp(&fake), config).unwrap();
+    assert!(queue.flush().await.unwrap());
+    queue.shutdown().await.unwrap();
+    assert_eq!(fake.requests().len(), sent);
+}
+
+#[tokio::test]
+async fn zero_interval_sends_continuously() {
+    let fake = Fake::new(200);
+    let mut config = config("zero-interval");
+    config.interval = Duration::ZERO;
+    let queue = PersistentQueue::open(amp(&fake), config).unwrap();
+    queue.track(event("a", "persistent")).unwrap();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert_eq!(fake.sent("user_id"), ["a"]);
+    queue.shutdown().await.unwrap();
+}
+
+#[tokio::test]
+async fn counts_replayed_events_in_queue_depth() {
+    let config = config("replay-depth");
+    let fake = Fake::new(503);
+    let queue = PersistentQueue::open(amp(&fake), config.clone()).unwrap();
+    for user_id in &["a", "b", "c"] {
+        queue.track(event(user_id, "persistent")).unwrap();
+    }
+    queue.shutdown().await.unwrap();
+
+    let amp = amp(&fake);
+    let queue = PersistentQueue::open(amp.clone(), config).unwrap();
+    assert_eq!(amp.stats().queue_depth, 3);
+    fake.set_status(200);
+    assert!(queue.flush().await.unwrap());
+    assert_eq!(amp.stats().queue_depth, 0);
+    queue.shutdown().await.unwrap();
+}
+
+#[tokio::test]
+async fn keeps_events_rejected_for_the_api_key() {
+    let fake = Fake::new(401);
+    let queue = PersistentQueue::open(amp(&fake), config("unauthorized")).unwrap();
+    queue.track(event("a", "persistent")).unwrap();
+    assert!(!queue.flush().await.unwrap());
+
+    fake.set_status(200);
+    assert!(queue.flush().await.unwrap());
+    queue.shutdown().await.unwrap();
+    assert_eq!(fake.sent("user_id"), ["a", "a"]);
+    let ids = fake.sent("insert_id");
+    assert!(!ids[0].is_empty());
+    assert_eq!(ids[0], ids[1]);
+}
+
+#[tokio::test]
+async fn keeps_events_with_invalid_or_missing_key() {
+    for body in &[
+        r#"{"code": 400, "error": "Invalid API key: bad"}"#,
+        r#"{"code": 400, "error": "Request missing required field", "missing_field": "api_key"}"#,
+    ] {
+        let queue =
+            PersistentQueue::open(amp(&Fake::with_body(400, body)), config("bad-key")).unwrap();
+        queue.track(event("a", "persistent")).unwrap();
+        assert!(!queue.flush().await.unwrap());
+        queue.shutdown().await.unwrap();
+    }
+}
+
+#[tokio::test]
+async fn skips_corrupted_and_torn_lines() {
+    let config = config("corrupted");
+    let mut disk = DiskQueue::open(&config).unwrap();
+    disk.push(&event("a", "persistent")).unwrap();
+    drop(disk);
+    let segment = std::fs::read_dir(&config.dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .find(|path| path.extension().unwrap() == "events")
+        .unwrap();
+    let mut data = std::fs::read(&segment).unwrap();
+    data.extend_from_slice(b"not an event\n{\"user_id\": \"b\"}\n{\"user_id\": \"tor");
+    std::fs::write(&segment, data).unwrap();
+
+    let fake = Fake::new(200);
+    let queue = PersistentQueue::open(amp(&fake), config).unwrap();
+    queue.track(event("c", "persistent")).unwrap();
+    assert!(queue.flush().await.unwrap());
+    queue.shutdown().await.unwrap();
+    assert_eq!(fake.sent("user_id"), ["a", "b", "c"]);
+}
+
+#[test]
+fn rotates_segments_and_caps_size() {
+    let mut config = config("rotation");
+    config.max_segment_bytes = 100;
+    config.max_total_bytes = 400;
+    let mut disk = DiskQueue::open(&config).unwrap();
+    let mut pushed = 0;
+    loop {
+        match disk.push(&event("rotation", "persistent")) {
+            Ok(()) => pushed += 1,
+            Err(AmplitudeError::QueueFull) => break,
+            Err(err) => panic!("unexpected error: {}", err),
+        }
+    }
+    assert!(disk.total_bytes() <= 400);
+    let segments = std::fs::read_dir(&config.dir).unwrap().count();
+    assert!(segments > 1);
+
+    let (events, cursor) = disk.peek(usize::MAX).unwrap();
+    assert_eq!(events.len(), pushed);
+    disk.ack(cursor).unwrap();
+    assert!(disk.total_bytes() < 100);
+    assert!(disk.push(&event("rotation", "persistent")).is_ok());
+}
@@ -0,0 +1,148 @@
+mod common;
+
+use std::time::Duration;
+
+use amplitude::{Amp, AmpQueue, AmplitudeError, Limits, QueueConfig};
+use common::{event, Fake, Hanging};
+
+fn config() -> QueueConfig {
+    QueueConfig {
+        max_events: 100,
+        max_bytes: 1024 * 1024,
+        interval: Duration::from_secs(3600),
+    }
+}
+
+#[tokio::test]
+async fn shutdown_sends_pending_events() {
+    let fake = Fake::new(200);
+    let mut amp = Amp::new("key");
+    amp.set_transport(fake.clone());
+    let queue = AmpQueue::new(amp, config());
+    queue.track(event("a", "queue")).unwrap();
+    queue.track(event("b", "queue")).unwrap();
+
+    let summary = queue.shutdown().await.unwrap();
+    assert_eq!(summary.delivered, 2);
+    assert!(summary.undelivered.is_empty());
+    assert_eq!(fake.sent("user_id"), ["a", "b"]);
+    assert!(matches!(
+        queue.track(event("c", "queue")),
+        Err(AmplitudeError::QueueClosed)
+    ));
+}
+
+#[tokio::test]
+async fn zero_interval_flushes_continuously() {
+    let fake = Fake::new(200);
+    let mut amp = Amp::new("key");
+    amp.set_transport(fake.clone());
+    let queue = AmpQueue::new(
+        amp,
+        QueueConfig {
+            interval: Duration::ZERO,
+            ..config()
+        },
+    );
+    queue.track(event("a", "queue")).unwrap();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert_eq!(fake.sent("user_id"), ["a"]);
+    assert_eq!(queue.shutdown().await.unwrap().delivered, 0);
+}
+
+#[tokio::test]
+async fn flush_reports_the_outcome() {
+    let fake = Fake::new(200);
+    let mut amp = Amp::new("key");
+    amp.set_transport(fake.clone());
+    let queue = AmpQueue::new(amp, config());
+    // lets the worker take the immediate first tick of its interval
+    tokio::time::sleep(Duration::from_millis(10)).await;
+    assert!(queue.flush().await.unwrap().delivered.is_empty());
+    queue.track(event("a", "queue")).unwrap();
+    assert_eq!(
+        queue.flush().await.unwrap().delivered,
+        vec![event("a", "queue")]
+    );
+
+    fake.set_status(503);
+    queue.track(event("b", "queue")).unwrap();
+    let report = queue.flush().await.unwrap();
+    assert!(!report.is_ok());
+    assert!(report.is_retryable());
+
+    let mut amp = Amp::new("key");
+    amp.set_transport(Fake::failing_after(200, 0));
+    let queue = AmpQueue::new(amp, config());
+    tokio::time::sleep(Duration::from_millis(10)).await;
+    queue.track(event("c", "queue")).unwrap();
+    assert!(matches!(
+        queue.flush().await,
+        Err(AmplitudeError::NetworkError(_))
+    ));
+}
+
+#[tokio::test]
+async fn shutdown_gives_up_after_timeout() {
+    let mut amp = Amp::new("key");
+    amp.set_transport(Hanging);
+    let queue = AmpQueue::new(amp, config());
+    queue.track(event("a", "queue")).unwrap();
+
+    let summary = queue
+        .shutdown_timeout(Duration::from_millis(50))
+        .await
+        .unwrap();
+    assert_eq!(summary.delivered, 0);
+    assert_eq!(summary.undelivered, vec![event("a", "queue")]);
+}
+
+#[tokio::test]
+async fn shutdown_cuts_off_a_flush_in_progress() {
+    let mut amp = Amp::new("key");
+    amp.set_transport(Hanging);
+    let queue = AmpQueue::new(
+        amp,
+        QueueConfig {
+            max_events: 2,
+            ..config()
+        },
+    );
+    queue.track(event("a", "queue")).unwrap();
+    queue.track(event("b", "queue")).unwrap();
+    queue.track(event("c", "queue")).unwrap();
+
+    let summary = tokio::time::timeout(
+        Duration::from_secs(5),
+        queue.shutdown_timeout(Duration::from_millis(50)),
+    )
+    .await
+    .expect("shutdown does not wait for the flush in progress")
+    .unwrap();
+    assert_eq!(summary.delivered, 0);
+    assert_eq!(
+        summary.undelivered,
+        vec![
+            event("a", "queue"),
+            event("b", "queue"),
+            event("c", "queue")
+        ]
+    );
+}
+
+#[tokio::test]
+async fn shutdown_counts_chunks_delivered_before_a_failure() {
+    let mut amp = Amp::new("key");
+    amp.set_transport(Fake::failing_after(200, 1))
+        .set_limits(Limits {
+            max_events: 1,
+            ..Limits::SINGLE
+        });
+    let queue = AmpQueue::new(amp, config());
+    queue.track(event("a", "queue")).unwrap();
+    queue.track(event("b", "queue")).unwrap();
+
+    let summary = queue.shutdown().await.unwrap();
+    assert_eq!(summary.delivered, 1);
+    assert_eq!(summary.undelivered, vec![event("b", "queue")]);
+}
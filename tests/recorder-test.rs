@@ -0,0 +1,57 @@
+mod common;
+
+use amplitude::response::AmplitudeResponse;
+use amplitude::{recorder, Amp, Recorder};
+use common::{event, temp_dir, Fake};
+
+#[tokio::test]
+async fn records_bodies_in_memory_without_posting() {
+    let fake = Fake::new(500);
+    let recorder = Recorder::memory();
+    let mut amp = Amp::new("key");
+    amp.set_transport(fake.clone())
+        .set_recorder(recorder.clone());
+    let report = amp
+        .send(vec![event("a", "recorded"), event("b", "recorded")])
+        .await
+        .unwrap();
+
+    assert!(fake.requests().is_empty());
+    assert_eq!(
+        report.delivered,
+        vec![event("a", "recorded"), event("b", "recorded")]
+    );
+    match &report.responses[0] {
+        AmplitudeResponse::Ok(ok) => assert_eq!(ok.events_ingested(), Some(2)),
+        response => panic!("unexpected response {:?}", response),
+    }
+    let recorded = recorder.recorded();
+    assert_eq!(recorded.len(), 1);
+    let body: serde_json::Value = serde_json::from_str(&recorded[0]).unwrap();
+    assert!(body.get("api_key").is_none());
+    assert_eq!(body["events"][1]["user_id"], "b");
+}
+
+#[tokio::test]
+async fn replays_recorded_file() {
+    let path = temp_dir("recorder").join("requests.ndjson");
+    let mut amp = Amp::new("secret-key");
+    amp.set_recorder(Recorder::file(&path).unwrap());
+    amp.send(vec![event("a", "recorded")]).await.unwrap();
+    amp.send(vec![event("b", "recorded")]).await.unwrap();
+    assert!(!std::fs::read_to_string(&path)
+        .unwrap()
+        .contains("secret-key"));
+
+    let fake = Fake::new(200);
+    let mut amp = Amp::new("real key");
+    amp.set_transport(fake.clone());
+    let report = recorder::replay(&amp, &path).await.unwrap();
+    assert_eq!(
+        report.delivered,
+        vec![event("a", "recorded"), event("b", "recorded")]
+    );
+    let requests = fake.requests();
+    assert_eq!(requests.len(), 1);
+    assert_eq!(requests[0]["api_key"], "real key");
+}
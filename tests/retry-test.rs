@@ -0,0 +1,63 @@
+#![cfg(feature = "reqwest")]
+
+mod common;
+
+use std::time::Duration;
+
+use amplitude::response::AmplitudeResponse;
+use amplitude::{Amp, AmplitudeError, RetryPolicy};
+use common::{event, Stub, OK, SERVER_ERROR, UNAVAILABLE};
+
+fn policy(max_attempts: u32) -> RetryPolicy {
+    RetryPolicy {
+        max_attempts,
+        base_delay: Duration::from_millis(1),
+        max_delay: Duration::from_millis(5),
+        jitter: 0.5,
+    }
+}
+
+#[tokio::test]
+async fn retries_until_success() {
+    let stub = Stub::start(vec![UNAVAILABLE, SERVER_ERROR, OK]).await;
+    let mut amp = Amp::new("key");
+    amp.set_base_url(&stub.url).set_retry_policy(policy(5));
+    let report = amp.send_one(event("retry-user", "retry")).await.unwrap();
+    assert_eq!(report.attempts, 3);
+    assert!(report.is_ok());
+    assert_eq!(stub.requests().len(), 3);
+}
+
+#[tokio::test]
+async fn gives_up_after_max_attempts() {
+    let stub = Stub::start(vec![SERVER_ERROR]).await;
+    let mut amp = Amp::new("key");
+    amp.set_base_url(&stub.url).set_retry_policy(policy(2));
+    let report = amp.send_one(event("retry-user", "retry")).await.unwrap();
+    assert_eq!(report.attempts, 2);
+    assert!(matches!(
+        report.responses[0],
+        AmplitudeResponse::ServerError(_)
+    ));
+}
+
+#[tokio::test]
+async fn no_retries_by_default() {
+    let stub = Stub::start(vec![SERVER_ERROR, OK]).await;
+    let mut amp = Amp::new("key");
+    amp.set_base_url(&stub.url);
+    let report = amp.send_one(event("retry-user", "retry")).await.unwrap();
+    assert_eq!(report.attempts, 1);
+    assert!(!report.is_ok());
+}
+
+#[tokio::test]
+async fn network_errors_are_retried() {
+    let mut amp = Amp::new("key");
+    amp.set_base_url("http://127.0.0.1:1/")
+        .set_retry_policy(policy(3));
+    match amp.send_one(event("retry-user", "retry")).await {
+        Err(AmplitudeError::RetriesExhausted { attempts, .. }) => assert_eq!(attempts, 3),
+        other => panic!("unexpected result: {:?}", other),
+    }
+}
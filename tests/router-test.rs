@@ -0,0 +1,81 @@
+mod common;
+
+use amplitude::{Amp, AmpRouter};
+use common::{event, Fake};
+
+fn amp(fake: &Fake) -> Amp {
+    let mut amp = Amp::new("key");
+    amp.set_transport(fake.clone());
+    amp
+}
+
+#[tokio::test]
+async fn routes_events_to_projects() {
+    let (prod, internal, billing) = (Fake::new(200), Fake::new(200), Fake::new(500));
+    let mut router = AmpRouter::new();
+    router
+        .add_project("prod", amp(&prod))
+        .add_project("internal", amp(&internal))
+        .add_project("billing", amp(&billing))
+        .route_by_tag(|event| {
+            (event.get_event_type() == Some("invoice")).then(|| "billing".to_string())
+        })
+        .route("internal", |event| {
+            event
+                .get_user_id()
+                .is_some_and(|id| id.starts_with("staff"))
+        })
+        .default_project("prod");
+
+    let report = router
+        .send(vec![
+            event("user-1", "click"),
+            event("staff-1", "click"),
+            event("staff-2", "invoice"),
+            event("user-2", "click"),
+        ])
+        .await;
+
+    assert!(!report.is_ok());
+    assert!(report.unrouted.is_empty());
+    assert_eq!(prod.sent("user_id"), vec!["user-1", "user-2"]);
+    assert_eq!(internal.sent("user_id"), vec!["staff-1"]);
+    assert_eq!(billing.sent("user_id"), vec!["staff-2"]);
+    assert!(report.projects["prod"].as_ref().unwrap().is_ok());
+    assert!(!report.projects["billing"].as_ref().unwrap().is_ok());
+}
+
+#[tokio::test]
+async fn keeps_unrouted_events() {
+    let fake = Fake::new(200);
+    let mut router = AmpRouter::new();
+    router
+        .add_project("prod", amp(&fake))
+        .route("prod", |event| event.get_event_type() == Some("click"))
+        .route_by_tag(|_| Some("missing".to_string()));
+
+    let report = router.send(vec![event("user-1", "view")]).await;
+    assert_eq!(report.unrouted, vec![event("user-1", "view")]);
+    assert!(report.projects.is_empty());
+    assert!(fake.requests().is_empty());
+}
+
+#[tokio::test]
+async fn does_not_fall_back_when_tagged_project_is_unknown() {
+    let (prod, other) = (Fake::new(200), Fake::new(200));
+    let mut router = AmpRouter::new();
+    router
+        .add_project("prod", amp(&prod))
+        .add_project("other", amp(&other))
+        .route_by_tag(|event| (event.get_event_type() == Some("click")).then(|| "prdo".to_string()))
+        .route("prod", |event| event.get_event_type() == Some("click"))
+        .default_project("other");
+
+    let report = router
+        .send(vec![event("user-1", "click"), event("user-2", "view")])
+        .await;
+    assert!(!report.is_ok());
+    assert_eq!(report.unrouted, vec![event("user-1", "click")]);
+    assert!(prod.requests().is_empty());
+    assert_eq!(other.sent("user_id"), vec!["user-2"]);
+}
@@ -31,7 +31,7 @@ async fn map() {
         interests: Vec<String>,
     }
 
-    let mut amp = Amp::from_env().unwrap();
+    let amp = Amp::from_env().unwrap();
     let mut event = Event::new();
     event
         .user_id("tetd")
@@ -70,13 +70,13 @@ async fn event_json() {
 
 #[tokio::test]
 async fn raw() -> Result<(), Box<dyn std::error::Error>> {
-    let mut amp = Amp::from_env()?;
-    let response = amp.send_one(json!(
+    let amp = Amp::from_env()?;
+    let response = amp.send_one(Event::from_json(json!(
         {
             "user_id": "46688",
             "event_type": "ollahcoyg"
         }
-    )).await?;
+    ))?).await?;
     eprintln!("response = {:#?}", response);
 
     Ok(())
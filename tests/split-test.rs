@@ -0,0 +1,46 @@
+#![cfg(feature = "reqwest")]
+
+mod common;
+
+use amplitude::{Amp, Reason};
+use common::{event, Stub, OK};
+
+const TOO_LARGE: (u16, &str) = (413, r#"{"code": 413, "error": "Payload too large"}"#);
+
+#[tokio::test]
+async fn bisects_until_single_event() {
+    // [a, big, c, d] -> [a, big] -> [a], [big] -> [c, d]
+    let stub = Stub::start(vec![TOO_LARGE, TOO_LARGE, OK, TOO_LARGE, OK]).await;
+    let mut amp = Amp::new("key");
+    amp.set_base_url(&stub.url).split_too_large(true);
+    let events = vec![
+        event("a", "split"),
+        event("big", "split"),
+        event("c", "split"),
+        event("d", "split"),
+    ];
+    let report = amp.send(events.clone()).await.unwrap();
+
+    assert_eq!(report.attempts, 5);
+    assert_eq!(
+        report.delivered,
+        vec![events[0].clone(), events[2].clone(), events[3].clone()]
+    );
+    assert_eq!(report.rejected.len(), 1);
+    assert_eq!(report.rejected[0].event, events[1]);
+    assert!(matches!(report.rejected[0].reason, Reason::TooLarge));
+}
+
+#[tokio::test]
+async fn does_not_split_by_default() {
+    let stub = Stub::start(vec![TOO_LARGE, OK]).await;
+    let mut amp = Amp::new("key");
+    amp.set_base_url(&stub.url);
+    let report = amp
+        .send(vec![event("a", "split"), event("b", "split")])
+        .await
+        .unwrap();
+    assert_eq!(report.attempts, 1);
+    assert_eq!(report.rejected.len(), 2);
+    assert!(report.delivered.is_empty());
+}
@@ -0,0 +1,63 @@
+mod common;
+
+use std::time::Duration;
+
+use amplitude::{Amp, AmpQueue, QueueConfig, RetryPolicy};
+use common::{event, Fake};
+
+#[tokio::test]
+async fn counts_requests_and_outcomes() {
+    let fake = Fake::new(503);
+    let mut amp = Amp::new("key");
+    amp.set_transport(fake.clone())
+        .set_retry_policy(RetryPolicy {
+            max_attempts: 2,
+            base_delay: Duration::from_millis(1),
+            max_delay: Duration::from_millis(1),
+            jitter: 0.0,
+        });
+    amp.send(vec![event("a", "stats"), event("b", "stats")])
+        .await
+        .unwrap();
+    fake.set_status(200);
+    amp.clone().send_one(event("c", "stats")).await.unwrap();
+
+    let stats = amp.stats();
+    assert_eq!(stats.requests, 3);
+    assert_eq!(stats.events_sent, 5);
+    assert!(stats.bytes_sent > 0);
+    assert_eq!(stats.events_delivered, 1);
+    assert_eq!(stats.events_failed, 2);
+    assert_eq!(stats.retries, 1);
+    assert_eq!(stats.responses["ServiceUnavailable"], 2);
+    assert_eq!(stats.responses["Ok"], 1);
+    assert_eq!(stats.latency.count, 3);
+    assert_eq!(stats.batch_size.count, 3);
+    assert_eq!(stats.batch_size.sum, 5.0);
+}
+
+#[tokio::test]
+async fn tracks_queue_depth() {
+    let fake = Fake::new(200);
+    let mut amp = Amp::new("key");
+    amp.set_transport(fake.clone());
+    let queue = AmpQueue::new(
+        amp.clone(),
+        QueueConfig {
+            interval: Duration::from_secs(3600),
+            ..QueueConfig::default()
+        },
+    );
+    // lets the worker take the immediate first tick of its interval
+    tokio::time::sleep(Duration::from_millis(10)).await;
+    for user_id in &["a", "b", "c"] {
+        queue.track(event(user_id, "stats")).unwrap();
+    }
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert_eq!(amp.stats().queue_depth, 3);
+
+    queue.flush().await.unwrap();
+    let stats = amp.stats();
+    assert_eq!(stats.queue_depth, 0);
+    assert_eq!(stats.events_delivered, 3);
+}
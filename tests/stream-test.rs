@@ -0,0 +1,54 @@
+mod common;
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use amplitude::{Amp, Limits};
+use common::{event, Fake, Hanging};
+use futures_util::{stream, StreamExt};
+
+fn limits(max_events: usize) -> Limits {
+    Limits {
+        max_events,
+        ..Limits::SINGLE
+    }
+}
+
+#[tokio::test]
+async fn sends_batches_of_stream() {
+    let fake = Fake::new(200);
+    let mut amp = Amp::new("key");
+    amp.set_transport(fake.clone()).set_limits(limits(2));
+    let reports: Vec<_> = amp
+        .send_stream(stream::iter(
+            (0..5).map(|i| event(&i.to_string(), "stream")),
+        ))
+        .collect()
+        .await;
+
+    let sizes: Vec<_> = reports
+        .into_iter()
+        .map(|report| report.unwrap().delivered.len())
+        .collect();
+    assert_eq!(sizes, vec![2, 2, 1]);
+    assert_eq!(fake.sent("user_id"), vec!["0", "1", "2", "3", "4"]);
+}
+
+#[tokio::test]
+async fn stops_pulling_events_while_requests_are_in_flight() {
+    let pulled = Arc::new(AtomicUsize::new(0));
+    let counter = pulled.clone();
+    let events = stream::iter(0..1000).map(move |i| {
+        counter.fetch_add(1, Ordering::SeqCst);
+        event(&i.to_string(), "stream")
+    });
+    let mut amp = Amp::new("key");
+    amp.set_transport(Hanging)
+        .set_limits(limits(10))
+        .set_concurrency(2);
+    let mut results = Box::pin(amp.send_stream(events));
+    let next = tokio::time::timeout(Duration::from_millis(50), results.next()).await;
+    assert!(next.is_err());
+    assert_eq!(pulled.load(Ordering::SeqCst), 20);
+}
@@ -0,0 +1,43 @@
+#![cfg(feature = "reqwest")]
+
+mod common;
+
+use std::time::Duration;
+
+use amplitude::{Amp, ThrottlePolicy};
+use common::{event, Stub, OK};
+
+const THROTTLED: (u16, &str) = (
+    429,
+    r#"{"code": 429, "error": "Too many requests", "eps_threshold": 10,
+        "throttled_users": {"hot": 20}, "throttled_events": [1]}"#,
+);
+
+#[tokio::test]
+async fn resends_throttled_events_separately() {
+    let stub = Stub::start(vec![THROTTLED, OK]).await;
+    let mut amp = Amp::new("key");
+    amp.set_base_url(&stub.url).set_throttle_policy(ThrottlePolicy {
+        max_resends: 1,
+        min_backoff: Duration::from_millis(1),
+        max_backoff: Duration::from_millis(10),
+    });
+    let report = amp
+        .send(vec![event("cold", "throttle"), event("hot", "throttle"), event("cool", "throttle")])
+        .await
+        .unwrap();
+    assert!(report.is_ok());
+    assert_eq!(report.attempts, 3);
+
+    let requests = stub.requests();
+    let users = |i: usize| -> Vec<String> {
+        requests[i]["events"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|event| event["user_id"].as_str().unwrap().to_string())
+            .collect()
+    };
+    assert_eq!(users(1), vec!["cold", "cool"]);
+    assert_eq!(users(2), vec!["hot"]);
+}
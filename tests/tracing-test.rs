@@ -0,0 +1,105 @@
+#![cfg(feature = "tracing")]
+
+mod common;
+
+use std::fmt::Debug;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use amplitude::{Amp, Event};
+use common::Fake;
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::{Event as TracingEvent, Metadata, Subscriber};
+use tracing_core::span::Current;
+
+/// Remembers the names and values of all fields of spans and events
+#[derive(Default)]
+struct Fields {
+    next_id: AtomicU64,
+    fields: Arc<Mutex<Vec<(String, String)>>>,
+    spans: Mutex<Vec<&'static Metadata<'static>>>,
+    entered: Mutex<Vec<Id>>,
+}
+
+impl Visit for &Fields {
+    fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+        self.fields
+            .lock()
+            .unwrap()
+            .push((field.name().to_string(), format!("{:?}", value)));
+    }
+}
+
+impl Subscriber for Fields {
+    fn enabled(&self, _: &Metadata<'_>) -> bool {
+        true
+    }
+
+    fn new_span(&self, span: &Attributes<'_>) -> Id {
+        span.record(&mut &*self);
+        self.spans.lock().unwrap().push(span.metadata());
+        Id::from_u64(self.next_id.fetch_add(1, Ordering::SeqCst) + 1)
+    }
+
+    fn current_span(&self) -> Current {
+        match self.entered.lock().unwrap().last() {
+            Some(id) => {
+                let metadata = self.spans.lock().unwrap()[id.into_u64() as usize - 1];
+                Current::new(id.clone(), metadata)
+            }
+            None => Current::none(),
+        }
+    }
+
+    fn record(&self, _: &Id, values: &Record<'_>) {
+        values.record(&mut &*self);
+    }
+
+    fn record_follows_from(&self, _: &Id, _: &Id) {}
+
+    fn event(&self, event: &TracingEvent<'_>) {
+        event.record(&mut &*self);
+    }
+
+    fn enter(&self, id: &Id) {
+        self.entered.lock().unwrap().push(id.clone());
+    }
+
+    fn exit(&self, _: &Id) {
+        self.entered.lock().unwrap().pop();
+    }
+}
+
+#[tokio::test]
+async fn traces_uploads_without_api_key() {
+    let subscriber = Fields::default();
+    let fields = subscriber.fields.clone();
+    let _guard = tracing::subscriber::set_default(subscriber);
+
+    let mut amp = Amp::new("secret-key");
+    amp.set_transport(Fake::new(200));
+    let mut event = Event::new();
+    event.user_id("traced-user").event_type("traced");
+    amp.send_one(event).await.unwrap();
+
+    let fields = fields.lock().unwrap();
+    let value = |name: &str| {
+        fields
+            .iter()
+            .find(|(field, _)| field == name)
+            .map(|(_, value)| value.clone())
+    };
+    assert_eq!(
+        value("endpoint").as_deref(),
+        Some("https://api2.amplitude.com/2/httpapi")
+    );
+    assert_eq!(value("events").as_deref(), Some("1"));
+    assert_eq!(value("attempt").as_deref(), Some("1"));
+    assert_eq!(value("status").as_deref(), Some("200"));
+    assert_eq!(value("response").as_deref(), Some("\"Ok\""));
+    assert!(value("bytes").is_some());
+    assert!(fields
+        .iter()
+        .all(|(_, value)| !value.contains("secret-key")));
+}
@@ -0,0 +1,156 @@
+mod common;
+
+use amplitude::response::AmplitudeResponse;
+use amplitude::transport::{BoxFuture, Request, Response, TransportError};
+use amplitude::{Amp, AmplitudeError, Limits, ServerZone, Transport};
+use common::{event, Fake};
+
+#[derive(Debug)]
+struct Offline;
+
+impl Transport for Offline {
+    fn post(&self, _: Request) -> BoxFuture<'_, Result<Response, TransportError>> {
+        Box::pin(async { Err("offline".into()) })
+    }
+}
+
+#[tokio::test]
+async fn posts_through_custom_transport() {
+    let fake = Fake::with_body(200, r#"{"code": 200, "events_ingested": 1}"#);
+    let mut amp = Amp::new("key");
+    amp.batch().set_transport(fake.clone());
+    let report = amp.send_one(event("fake-user", "fake")).await.unwrap();
+    assert!(report.is_ok());
+
+    let requests = fake.posted();
+    assert_eq!(requests.len(), 1);
+    assert_eq!(requests[0].url, "https://api2.amplitude.com/batch");
+    assert!(requests[0]
+        .headers
+        .contains(&("Content-Type".to_string(), "application/json".to_string())));
+    let body: serde_json::Value = serde_json::from_slice(&requests[0].body).unwrap();
+    assert_eq!(body["api_key"], "key");
+    assert_eq!(body["events"][0]["user_id"], "fake-user");
+}
+
+#[tokio::test]
+async fn maps_status_to_response() {
+    let mut amp = Amp::new("key");
+    amp.set_transport(Fake::with_body(503, r#"{"error": "down"}"#));
+    let report = amp.send_one(event("fake-user", "fake")).await.unwrap();
+    assert!(matches!(
+        report.responses[0],
+        AmplitudeResponse::ServiceUnavailable(_)
+    ));
+}
+
+#[tokio::test]
+async fn keeps_unexpected_responses() {
+    let mut amp = Amp::new("bad key");
+    amp.set_transport(Fake::with_body(401, "Unauthorized"));
+    let report = amp.send_one(event("fake-user", "fake")).await.unwrap();
+    assert!(!report.is_ok());
+    assert_eq!(report.rejected.len(), 1);
+    match &report.responses[0] {
+        AmplitudeResponse::Unexpected(unexpected) => {
+            assert_eq!(unexpected.status(), 401);
+            assert_eq!(unexpected.body(), "Unauthorized");
+        }
+        response => panic!("unexpected response {:?}", response),
+    }
+}
+
+#[tokio::test]
+async fn html_error_pages_keep_their_status() {
+    let mut amp = Amp::new("key");
+    amp.set_transport(Fake::with_body(
+        502,
+        "<html><body>Bad Gateway</body></html>",
+    ));
+    let report = amp.send_one(event("fake-user", "fake")).await.unwrap();
+    match &report.responses[0] {
+        AmplitudeResponse::Unexpected(unexpected) => {
+            assert_eq!(unexpected.status(), 502);
+            assert_eq!(unexpected.body(), "<html><body>Bad Gateway</body></html>");
+        }
+        response => panic!("unexpected response {:?}", response),
+    }
+    assert!(report.is_retryable());
+
+    amp.set_transport(Fake::with_body(200, "<html></html>"));
+    let report = amp.send_one(event("fake-user", "fake")).await.unwrap();
+    assert!(matches!(
+        report.responses[0],
+        AmplitudeResponse::Unexpected(_)
+    ));
+    assert!(report.delivered.is_empty());
+}
+
+#[tokio::test]
+async fn transport_errors_are_network_errors() {
+    let mut amp = Amp::new("key");
+    amp.set_transport(Offline);
+    let err = amp.send_one(event("fake-user", "fake")).await.unwrap_err();
+    assert!(matches!(err, AmplitudeError::NetworkError(_)));
+}
+
+#[tokio::test]
+async fn reports_chunks_delivered_before_a_failure() {
+    let mut amp = Amp::new("key");
+    amp.set_transport(Fake::failing_after(200, 2))
+        .set_limits(Limits {
+            max_events: 1,
+            ..Limits::SINGLE
+        });
+    let err = amp
+        .send(vec![
+            event("fake-user", "fake"),
+            event("fake-user", "fake"),
+            event("fake-user", "fake"),
+        ])
+        .await
+        .unwrap_err();
+    match err {
+        AmplitudeError::PartiallySent {
+            report,
+            unsent,
+            source,
+        } => {
+            assert_eq!(report.delivered.len(), 2);
+            assert_eq!(unsent, vec![event("fake-user", "fake")]);
+            assert!(matches!(*source, AmplitudeError::NetworkError(_)));
+        }
+        err => panic!("unexpected error {:?}", err),
+    }
+}
+
+#[tokio::test]
+async fn posts_to_server_zone() {
+    let fake = Fake::with_body(200, r#"{"code": 200}"#);
+    let mut amp = Amp::new("key");
+    amp.set_server_zone(ServerZone::EU)
+        .set_transport(fake.clone());
+    amp.send_one(event("fake-user", "fake")).await.unwrap();
+    amp.batch()
+        .send_one(event("fake-user", "fake"))
+        .await
+        .unwrap();
+    amp.set_base_url("http://localhost:8080/relay/")
+        .send_one(event("fake-user", "fake"))
+        .await
+        .unwrap();
+
+    let urls: Vec<_> = fake
+        .posted()
+        .iter()
+        .map(|request| request.url.clone())
+        .collect();
+    assert_eq!(
+        urls,
+        vec![
+            "https://api.eu.amplitude.com/2/httpapi",
+            "https://api.eu.amplitude.com/batch",
+            "http://localhost:8080/relay/batch",
+        ]
+    );
+}